@@ -0,0 +1,385 @@
+use crate::frame;
+use std::cmp;
+use std::f64::consts::PI;
+
+// Length of the windows the audio track is measured in, in seconds
+const WINDOW: f32 = 0.1;
+
+// Loudness in LUFS at which the loudness strip starts to show bars
+const SILENCE: f32 = -60.0;
+
+// Colors of the audio strip, as RGB
+const BACKGROUND: [u8; 3] = [24, 24, 24];
+const PEAK: [u8; 3] = [70, 130, 180];
+const RMS: [u8; 3] = [150, 200, 240];
+const QUIET: [u8; 3] = [40, 170, 60];
+const MEDIUM: [u8; 3] = [230, 200, 40];
+const LOUD: [u8; 3] = [220, 40, 30];
+
+// How the audio strip visualizes the audio track
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    // Peak and RMS amplitude of the samples, mirrored around the middle of the strip
+    Waveform,
+    // K-weighted loudness in LUFS, as bars which get taller and redder the louder the audio is
+    Loudness,
+}
+
+// Measurements of the samples in one window of the audio track
+#[derive(Clone, Copy, Default)]
+struct Window {
+    // Sum of the squared samples
+    power: f64,
+    // Sum of the squared samples after K-weighting them
+    weighted_power: f64,
+    // Number of samples
+    count: u64,
+    // Largest absolute sample value
+    peak: f32,
+}
+
+// Level of an audio track over time. Positions are in seconds, relative to the first frame of the
+// video, like the frames' timestamps.
+#[derive(Default)]
+pub struct Levels {
+    windows: Vec<Window>,
+    // Added to the stream time of samples to get their position
+    shift: f32,
+    // K-weighting filter, and the sample rate it was set up for
+    filter: Option<(u32, [Biquad; 2])>,
+}
+
+impl Levels {
+    // Prepare for the samples of an input file which starts at `position` in the video, and
+    // whose first frame is at `offset` in its stream
+    pub fn begin(&mut self, position: f32, offset: f32) {
+        self.shift = position - offset;
+        self.filter = None;
+    }
+
+    // Whether no samples have arrived, because the input has no audio track
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    // Add mono `samples` with the given sample `rate`, the first of which is at `time` in the
+    // stream
+    pub fn add(&mut self, time: f32, rate: u32, samples: &[f32]) {
+        if rate == 0 {
+            return;
+        }
+
+        // The filter's coefficients depend on the sample rate
+        if self.filter.as_ref().map(|(filter_rate, _)| *filter_rate) != Some(rate) {
+            self.filter = Some((rate, k_weighting(rate)));
+        }
+        let filter = &mut self
+            .filter
+            .as_mut()
+            .expect("Could not get K-weighting filter")
+            .1;
+
+        let start = f64::from(time + self.shift);
+        for (i, &sample) in samples.iter().enumerate() {
+            let sample = f64::from(sample);
+            let shelved = filter[0].process(sample);
+            let weighted = filter[1].process(shelved);
+
+            // Samples before the first frame are only used to settle the filter
+            let position = start + i as f64 / f64::from(rate);
+            if position < 0.0 {
+                continue;
+            }
+
+            let index = (position / f64::from(WINDOW)) as usize;
+            if index >= self.windows.len() {
+                self.windows.resize(index + 1, Window::default());
+            }
+            let window = &mut self.windows[index];
+            window.power += sample * sample;
+            window.weighted_power += weighted * weighted;
+            window.count += 1;
+            window.peak = window.peak.max(sample.abs() as f32);
+        }
+    }
+
+    // Combine the windows which overlap the range from `from` to `to` seconds. Ranges shorter
+    // than a window use the window they start in.
+    fn measure(&self, from: f32, to: f32) -> Window {
+        let first = (from.max(0.0) / WINDOW) as usize;
+        let last = cmp::max((to / WINDOW).ceil() as usize, first + 1);
+
+        self.windows
+            .iter()
+            .skip(first)
+            .take(last - first)
+            .fold(Window::default(), |sum, window| Window {
+                power: sum.power + window.power,
+                weighted_power: sum.weighted_power + window.weighted_power,
+                count: sum.count + window.count,
+                peak: sum.peak.max(window.peak),
+            })
+    }
+
+    // Loudness in LUFS from `from` to `to` seconds, as defined by ITU-R BS.1770 for a single
+    // channel. Returns `None` if there are no samples in that range.
+    pub fn loudness(&self, from: f32, to: f32) -> Option<f32> {
+        let window = self.measure(from, to);
+        if window.count == 0 {
+            return None;
+        }
+
+        let power = window.weighted_power / window.count as f64;
+        Some((-0.691 + 10.0 * power.max(1e-12).log10()) as f32)
+    }
+
+    // Ranges of at least `min_duration` seconds between `from` and `to`, in which the audio's peak
+    // level stays below `threshold` dBFS. Gaps without any samples count as silent.
+    pub fn silences(
+        &self,
+        from: f32,
+        to: f32,
+        threshold: f32,
+        min_duration: f32,
+    ) -> Vec<(f32, f32)> {
+        let amplitude = 10f32.powf(threshold / 20.0);
+        let first = (from.max(0.0) / WINDOW) as usize;
+        let last = (to / WINDOW).ceil() as usize;
+
+        let mut silences = vec![];
+        let mut silence_start = None;
+        for index in first..=last {
+            let silent = index < last
+                && match self.windows.get(index) {
+                    Some(window) => window.count == 0 || window.peak < amplitude,
+                    None => true,
+                };
+
+            let position = (index as f32 * WINDOW).max(from).min(to);
+            match (silence_start, silent) {
+                (None, true) => silence_start = Some(position),
+                (Some(start), false) => {
+                    if position - start >= min_duration {
+                        silences.push((start, position));
+                    }
+                    silence_start = None;
+                }
+                _ => {}
+            }
+        }
+
+        silences
+    }
+
+    // Draw a strip of `width`*`height` pixels, where each column shows the audio of the
+    // corresponding timeline column, which covers the range of `duration` seconds from `start`
+    pub fn render(
+        &self,
+        style: Style,
+        width: usize,
+        height: usize,
+        start: f32,
+        duration: f32,
+    ) -> frame::Frame {
+        let mut strip = frame::Frame::new(width, height);
+
+        {
+            let buffer = strip
+                .buffer
+                .get_mut()
+                .expect("Could not get mutable buffer for audio strip");
+            let mut data = buffer
+                .map_writable()
+                .expect("Could not get writable map of buffer for audio strip");
+
+            for x in 0..width {
+                let from = start + duration * x as f32 / width as f32;
+                let to = start + duration * (x + 1) as f32 / width as f32;
+
+                let column = match style {
+                    Style::Waveform => self.waveform_column(from, to, height),
+                    Style::Loudness => self.loudness_column(from, to, height),
+                };
+
+                for (y, color) in column.iter().enumerate() {
+                    let i = (y * width + x) * 4;
+                    // Frames are stored as BGRx
+                    data[i] = color[2];
+                    data[i + 1] = color[1];
+                    data[i + 2] = color[0];
+                    data[i + 3] = 255;
+                }
+            }
+        }
+
+        strip
+    }
+
+    // Colors of a waveform column: the peak amplitude, and the RMS amplitude inside of it
+    fn waveform_column(&self, from: f32, to: f32, height: usize) -> Vec<[u8; 3]> {
+        let window = self.measure(from, to);
+        let (peak, rms) = if window.count == 0 {
+            (0.0, 0.0)
+        } else {
+            (
+                window.peak.min(1.0),
+                ((window.power / window.count as f64).sqrt() as f32).min(1.0),
+            )
+        };
+
+        let middle = height as f32 / 2.0;
+        (0..height)
+            .map(|y| {
+                // Distance of the pixel's center from the middle, relative to half the height
+                let distance = ((y as f32 + 0.5) - middle).abs() / middle;
+                if distance <= rms {
+                    RMS
+                } else if distance <= peak {
+                    PEAK
+                } else {
+                    BACKGROUND
+                }
+            })
+            .collect()
+    }
+
+    // Colors of a loudness column: a bar from the bottom, whose height and color show the
+    // loudness between `SILENCE` and 0 LUFS
+    fn loudness_column(&self, from: f32, to: f32, height: usize) -> Vec<[u8; 3]> {
+        let level = match self.loudness(from, to) {
+            Some(loudness) => ((loudness - SILENCE) / -SILENCE).max(0.0).min(1.0),
+            None => 0.0,
+        };
+
+        let color = if level < 0.5 {
+            mix(QUIET, MEDIUM, level * 2.0)
+        } else {
+            mix(MEDIUM, LOUD, level * 2.0 - 1.0)
+        };
+        let bar = (level * height as f32).round() as usize;
+
+        (0..height)
+            .map(|y| if height - y <= bar { color } else { BACKGROUND })
+            .collect()
+    }
+}
+
+// Blend from color `a` to color `b`, with `t` between 0 and 1
+pub fn mix(a: [u8; 3], b: [u8; 3], t: f32) -> [u8; 3] {
+    let channel = |i: usize| (a[i] as f32 + (b[i] as f32 - a[i] as f32) * t).round() as u8;
+    [channel(0), channel(1), channel(2)]
+}
+
+// Second-order IIR filter, in transposed direct form II
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+// The K-weighting filter of ITU-R BS.1770 for the given sample `rate`: a high shelf, which models
+// the acoustic effect of the head, followed by a high-pass filter. BS.1770 only lists coefficients
+// for 48 kHz, so they are derived from the filters' analog parameters.
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = f64::from(rate);
+
+    let (f0, gain, q) = (
+        1_681.974_450_955_533,
+        3.999_843_853_973_347,
+        0.707_175_236_955_419_6,
+    );
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let (f0, q) = (38.135_470_876_024_44, 0.500_327_037_323_877_3);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let highpass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    [shelf, highpass]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::Levels;
+    use std::f32::consts::PI;
+
+    fn sine(frequency: f32, amplitude: f32, rate: u32, seconds: f32) -> Vec<f32> {
+        (0..(rate as f32 * seconds) as usize)
+            .map(|i| amplitude * (2.0 * PI * frequency * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_loudness() {
+        // A full scale sine at 997 Hz is specified to have a loudness of -3.01 LUFS
+        let mut levels = Levels::default();
+        levels.add(0.0, 48000, &sine(997.0, 1.0, 48000, 2.0));
+        let loudness = levels
+            .loudness(0.5, 1.5)
+            .expect("Could not measure loudness");
+        assert!((loudness + 3.01).abs() < 0.1, "loudness was {}", loudness);
+
+        // 20 dB quieter
+        let mut levels = Levels::default();
+        levels.add(0.0, 44100, &sine(997.0, 0.1, 44100, 2.0));
+        let loudness = levels
+            .loudness(0.5, 1.5)
+            .expect("Could not measure loudness");
+        assert!((loudness + 23.01).abs() < 0.1, "loudness was {}", loudness);
+    }
+
+    #[test]
+    fn test_positions() {
+        // The input's first frame is at 10 seconds, and it starts 5 seconds into the video
+        let mut levels = Levels::default();
+        levels.begin(5.0, 10.0);
+        levels.add(9.0, 8000, &[0.5; 16000]);
+
+        assert_eq!(levels.loudness(0.0, 3.9), None);
+        assert!(levels.loudness(4.0, 6.0).is_some());
+        assert_eq!(levels.loudness(6.0, 7.0), None);
+    }
+
+    #[test]
+    fn test_silences() {
+        // One second of sound, three seconds of near silence, and one second of sound
+        let mut samples = sine(440.0, 0.5, 8000, 1.0);
+        samples.extend(sine(440.0, 0.0001, 8000, 3.0));
+        samples.extend(sine(440.0, 0.5, 8000, 1.0));
+        let mut levels = Levels::default();
+        levels.add(0.0, 8000, &samples);
+
+        let silences = levels.silences(0.0, 5.0, -60.0, 2.0);
+        assert_eq!(silences.len(), 1);
+        let (from, to) = silences[0];
+        assert!((from - 1.0).abs() < 0.15 && (to - 4.0).abs() < 0.15);
+
+        assert!(levels.silences(0.0, 5.0, -60.0, 3.5).is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+use std::fs::File;
+use std::io::Write;
+
+// Every BIF file starts with these eight bytes
+const MAGIC: [u8; 8] = [0x89, 0x42, 0x49, 0x46, 0x0d, 0x0a, 0x1a, 0x0a];
+
+// Size of the fixed header, after which the index table starts
+const HEADER_SIZE: usize = 64;
+
+// Write a BIF ("Base Index Frames") archive, as used for trick play on Roku and Plex clients.
+//
+// `images` are the JPEG-encoded thumbnails, together with the time in seconds from which on they
+// are shown.
+pub fn write_to(filename: &str, images: &[(u32, Vec<u8>)]) -> Result<(), String> {
+    let mut file = match File::create(&filename) {
+        Ok(file) => file,
+        Err(e) => {
+            return Err(format!("Could not create '{}': {})", &filename, e));
+        }
+    };
+
+    match file.write_all(&encode(images)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+    }
+}
+
+// Assemble header, index table and image data of a BIF archive
+fn encode(images: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut bif = Vec::new();
+
+    // Header: magic number, version, number of images, and the timestamp multiplier in
+    // milliseconds, so that timestamps are in seconds. The rest is reserved and has to be zero.
+    bif.extend_from_slice(&MAGIC);
+    bif.extend_from_slice(&0u32.to_le_bytes());
+    bif.extend_from_slice(&(images.len() as u32).to_le_bytes());
+    bif.extend_from_slice(&1000u32.to_le_bytes());
+    bif.resize(HEADER_SIZE, 0);
+
+    // Index table: one (timestamp, offset) pair per image, plus a terminating entry which points
+    // to the end of the last image
+    let mut offset = HEADER_SIZE + 8 * (images.len() + 1);
+    for (timestamp, image) in images {
+        bif.extend_from_slice(&timestamp.to_le_bytes());
+        bif.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += image.len();
+    }
+    bif.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+    bif.extend_from_slice(&(offset as u32).to_le_bytes());
+
+    // Finally, the concatenated images
+    for (_, image) in images {
+        bif.extend_from_slice(&image);
+    }
+
+    bif
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_encode() {
+        use crate::bif::encode;
+
+        let bif = encode(&[(0, vec![1, 2, 3]), (10, vec![4, 5])]);
+
+        assert_eq!(&bif[0..8], &crate::bif::MAGIC);
+        assert_eq!(&bif[12..16], &[2, 0, 0, 0]);
+        assert_eq!(&bif[16..20], &1000u32.to_le_bytes());
+        assert_eq!(&bif[64..72], &[0, 0, 0, 0, 88, 0, 0, 0]);
+        assert_eq!(&bif[72..80], &[10, 0, 0, 0, 91, 0, 0, 0]);
+        assert_eq!(&bif[80..88], &[0xff, 0xff, 0xff, 0xff, 93, 0, 0, 0]);
+        assert_eq!(&bif[88..], &[1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::frame;
+use crate::source;
+use crate::Config;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+// Number of bins per color channel in the histograms which are compared
+const BINS: usize = 16;
+
+// Frames per second which are analyzed, so that cuts are found to about a tenth of a second
+pub const FRAMERATE: f32 = 10.0;
+
+// Timecodes in EDL files count frames, so they need a framerate, even if the input doesn't
+// report one
+const DEFAULT_EDL_FRAMERATE: u32 = 25;
+
+// Color of the cut markers on the timeline, as RGB
+const MARKER: [u8; 3] = [230, 30, 30];
+
+// A hard cut from one shot to the next
+pub struct Cut {
+    // Position of the first frame of the new shot in seconds, like the frames' timestamps
+    pub position: f32,
+    // How different the frames before and after the cut are, between 0 and 1
+    pub score: f32,
+}
+
+// Finds cuts between shots, by comparing the color histograms of consecutive frames. Frames have
+// to be added in order.
+pub struct Detector {
+    // Histogram differences from this value on count as cuts
+    threshold: f32,
+    // Histogram of the previous frame
+    previous: Option<Vec<f32>>,
+    // Cuts found so far, in order
+    pub cuts: Vec<Cut>,
+}
+
+impl Detector {
+    // Initializes a detector, which finds cuts where the histograms of consecutive frames differ
+    // by at least `threshold`
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            previous: None,
+            cuts: vec![],
+        }
+    }
+
+    // Compare `frame` to the previous one, and remember a cut if they differ enough
+    pub fn add(&mut self, frame: &frame::Frame) {
+        let histogram = histogram(frame);
+
+        if let Some(ref previous) = self.previous {
+            let score = difference(previous, &histogram);
+            if score >= self.threshold {
+                self.cuts.push(Cut {
+                    position: frame
+                        .pts
+                        .expect("Could not get PTS of frame for cut detection"),
+                    score,
+                });
+            }
+        }
+
+        self.previous = Some(histogram);
+    }
+}
+
+// Count the blue, green and red values of the pixels of `frame` in `BINS` bins each. The counts
+// are normalized, so that each channel sums up to 1.
+fn histogram(frame: &frame::Frame) -> Vec<f32> {
+    let map = frame
+        .buffer
+        .map_readable()
+        .expect("Could not get readable map of buffer for histogram");
+    let data = map.as_slice();
+
+    let mut histogram = vec![0.0; 3 * BINS];
+    for pixel in data.chunks_exact(4) {
+        for channel in 0..3 {
+            histogram[channel * BINS + pixel[channel] as usize * BINS / 256] += 1.0;
+        }
+    }
+
+    let pixels = (frame.width * frame.height) as f32;
+    for count in histogram.iter_mut() {
+        *count /= pixels;
+    }
+    histogram
+}
+
+// Returns how different two histograms are, between 0 for identical ones, and 1 if no pixel
+// values are in common
+fn difference(a: &[f32], b: &[f32]) -> f32 {
+    let distance: f32 = a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum();
+    // Each channel contributes at most 2
+    distance / 6.0
+}
+
+// Write the `cuts` to `filename`. The format follows from its extension: CSV with one line per
+// cut, an EDL with one event per shot, or WebVTT chapters with one chapter per shot.
+pub fn write_to(
+    filename: &str,
+    config: &Config,
+    source: &source::VideoSource,
+    cuts: &[Cut],
+) -> Result<(), String> {
+    let positions = positions(config, source, cuts);
+    let start = crate::timestamp_offset(config);
+    let end = start + source.duration;
+
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+    let content = match extension {
+        "csv" => csv(cuts, &positions),
+        "edl" => edl(
+            &positions,
+            start,
+            end,
+            source.meta.framerate,
+            &config.input_filename,
+        ),
+        _ => vtt(&positions, start, end),
+    };
+
+    let mut file = match File::create(filename) {
+        Ok(file) => file,
+        Err(e) => {
+            return Err(format!("Could not create '{}': {})", &filename, e));
+        }
+    };
+
+    match file.write_all(content.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+    }
+}
+
+// Returns the positions of the `cuts` in seconds, which are offset like all other timestamps in
+// the outputs
+pub fn positions(config: &Config, source: &source::VideoSource, cuts: &[Cut]) -> Vec<f32> {
+    let offset = crate::timestamp_offset(config) - source.start;
+    cuts.iter().map(|cut| cut.position + offset).collect()
+}
+
+// One line per cut, with its number, its position in seconds and as a timestamp, and its score
+fn csv(cuts: &[Cut], positions: &[f32]) -> String {
+    let mut content = String::from("cut,position,timestamp,score\n");
+    for (i, (cut, position)) in cuts.iter().zip(positions).enumerate() {
+        content.push_str(&format!(
+            "{},{:.3},{},{:.3}\n",
+            i + 1,
+            position,
+            crate::timestamp((position * 1000.0).round() as i32),
+            cut.score
+        ));
+    }
+    content
+}
+
+// An edit decision list in the CMX 3600 format, which contains each shot as an event
+fn edl(positions: &[f32], start: f32, end: f32, framerate: Option<f32>, title: &str) -> String {
+    // Timecodes of fractional framerates like 29.97 count whole frames, without dropping any
+    let framerate = framerate.map_or(DEFAULT_EDL_FRAMERATE, |framerate| framerate.round() as u32);
+
+    let mut content = format!("TITLE: {}\nFCM: NON-DROP FRAME\n\n", title);
+    for (i, (from, to)) in shots(positions, start, end).into_iter().enumerate() {
+        // The shots are recorded one after another, like in the input
+        content.push_str(&format!(
+            "{:03}  AX       V     C        {} {} {} {}\n* FROM CLIP NAME: {}\n\n",
+            i + 1,
+            timecode(from, framerate),
+            timecode(to, framerate),
+            timecode(from - start, framerate),
+            timecode(to - start, framerate),
+            title
+        ));
+    }
+    content
+}
+
+// WebVTT chapters, one for each shot
+fn vtt(positions: &[f32], start: f32, end: f32) -> String {
+    let mut content = String::from("WEBVTT\n\n");
+    for (i, (from, to)) in shots(positions, start, end).into_iter().enumerate() {
+        content.push_str(&format!(
+            "{}\n{} --> {}\nShot {}\n\n",
+            i + 1,
+            crate::timestamp((from * 1000.0).round() as i32),
+            crate::timestamp((to * 1000.0).round() as i32),
+            i + 1
+        ));
+    }
+    content
+}
+
+// Returns start and end of the shots between the cut `positions`, from `start` to `end`
+fn shots(positions: &[f32], start: f32, end: f32) -> Vec<(f32, f32)> {
+    let mut boundaries = vec![start];
+    boundaries.extend(positions.iter().cloned());
+    boundaries.push(end);
+    boundaries
+        .windows(2)
+        .map(|shot| (shot[0], shot[1]))
+        .filter(|(from, to)| to > from)
+        .collect()
+}
+
+// Format `seconds` as an SMPTE timecode "HH:MM:SS:FF" with the given `framerate`
+fn timecode(seconds: f32, framerate: u32) -> String {
+    let frames = (seconds.max(0.0) * framerate as f32).round() as u32;
+    format!(
+        "{:02}:{:02}:{:02}:{:02}",
+        frames / framerate / 3600,
+        frames / framerate / 60 % 60,
+        frames / framerate % 60,
+        frames % framerate
+    )
+}
+
+// Draw a vertical line onto the visual `timeline` for each of the `cuts`
+pub fn draw_markers(
+    timeline: &mut frame::Frame,
+    config: &Config,
+    source: &source::VideoSource,
+    cuts: &[Cut],
+) {
+    let buffer = timeline
+        .buffer
+        .get_mut()
+        .expect("Could not get mutable buffer for cut markers");
+    let mut data = buffer
+        .map_writable()
+        .expect("Could not get writable map of buffer for cut markers");
+
+    for cut in cuts {
+        let x = crate::column_index(cut.position - source.start, source.duration, config);
+        for y in 0..config.height {
+            let i = (y * config.width + x) * 4;
+            // Frames are stored as BGRx
+            data[i] = MARKER[2];
+            data[i + 1] = MARKER[1];
+            data[i + 2] = MARKER[0];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_difference() {
+        use crate::cuts::{difference, BINS};
+
+        let mut black = vec![0.0; 3 * BINS];
+        let mut white = vec![0.0; 3 * BINS];
+        for channel in 0..3 {
+            black[channel * BINS] = 1.0;
+            white[channel * BINS + BINS - 1] = 1.0;
+        }
+
+        assert_eq!(difference(&black, &black), 0.0);
+        assert_eq!(difference(&black, &white), 1.0);
+    }
+
+    #[test]
+    fn test_timecode() {
+        use crate::cuts::timecode;
+        assert_eq!(timecode(0.0, 25), "00:00:00:00");
+        assert_eq!(timecode(4.48, 25), "00:00:04:12");
+        assert_eq!(timecode(3723.5, 30), "01:02:03:15");
+    }
+
+    #[test]
+    fn test_shots() {
+        use crate::cuts::shots;
+        assert_eq!(
+            shots(&[2.0, 5.5], 0.0, 10.0),
+            vec![(0.0, 2.0), (2.0, 5.5), (5.5, 10.0)]
+        );
+        assert_eq!(shots(&[], 1.0, 3.0), vec![(1.0, 3.0)]);
+    }
+}
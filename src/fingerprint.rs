@@ -0,0 +1,357 @@
+use crate::frame;
+use crate::source;
+use crate::Config;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+// Height of the frames which are decoded to fingerprint an input. Only their average color is
+// needed, so they can be tiny.
+const HEIGHT: usize = 32;
+
+// Alignments are only considered if the fingerprints overlap by at least this share of the
+// shorter one
+const MIN_OVERLAP: f32 = 0.5;
+
+// Range of speed differences which are tried when aligning fingerprints, and the steps in which
+// they are tried, first coarsely, then around the best coarse result. The speedup from film to
+// PAL, for example, is about 4%.
+const MAX_SCALE: f32 = 1.25;
+const COARSE_SCALE_STEP: f32 = 1.01;
+const FINE_SCALE_STEP: f32 = 1.001;
+
+// A compact signature of a video: the average color of the frames at evenly spaced times
+pub struct Fingerprint {
+    // Positions of the samples in seconds, like all timestamps in the outputs
+    times: Vec<f32>,
+    // Average color of each sample, as RGB from 0 to 255
+    colors: Vec<[f32; 3]>,
+}
+
+// How two fingerprints match best: a position `t` in the second one corresponds to the position
+// `scale * t + shift` in the first one
+pub struct Alignment {
+    // How similar the fingerprints are where they overlap, from 0 to 1
+    pub similarity: f32,
+    pub scale: f32,
+    pub shift: f32,
+}
+
+impl Fingerprint {
+    // Fingerprint the visual `timeline`, whose columns are one sample each
+    pub fn from_timeline(timeline: &frame::Frame, config: &Config, duration: f32) -> Self {
+        let map = timeline
+            .buffer
+            .map_readable()
+            .expect("Could not get readable map of timeline for fingerprint");
+        let data = map.as_slice();
+
+        let colors = (0..config.width)
+            .map(|x| {
+                let mut sums = [0.0; 3];
+                for y in 0..config.height {
+                    let i = (y * config.width + x) * 4;
+                    // Frames are stored as BGRx
+                    for (channel, sum) in sums.iter_mut().enumerate() {
+                        *sum += f32::from(data[i + 2 - channel]);
+                    }
+                }
+                let count = config.height.max(1) as f32;
+                [sums[0] / count, sums[1] / count, sums[2] / count]
+            })
+            .collect();
+
+        Self {
+            times: sample_times(config, duration),
+            colors,
+        }
+    }
+
+    // Fingerprint the input `filename`, which is decoded like the input of a normal run, or read
+    // from a file written by `write_to`, if it has the extension `.csv`
+    pub fn load(
+        filename: &str,
+        config: &Config,
+        options: &source::Options,
+    ) -> Result<Self, String> {
+        if Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            == Some("csv")
+        {
+            return Self::read(filename);
+        }
+
+        let mut source = source::VideoSource::new(
+            &[String::from(filename)],
+            &[None],
+            HEIGHT,
+            config.width,
+            options,
+        )?;
+        let start = source.start;
+
+        // Like on the timeline, the first frame of each column is used
+        let mut frames = vec![];
+        for frame in &mut source {
+            frames.push((
+                frame
+                    .pts
+                    .expect("Could not get PTS of frame for fingerprint"),
+                average(&frame),
+            ));
+        }
+        if frames.is_empty() {
+            return Err(format!("'{}' does not contain any frames.", filename));
+        }
+
+        // The duration might only be known now
+        let duration = source.duration;
+        let mut colors: Vec<Option<[f32; 3]>> = vec![None; config.width];
+        for (pts, color) in frames {
+            let i = crate::column_index(pts - start, duration, config);
+            colors[i].get_or_insert(color);
+        }
+
+        // Columns without a frame repeat the previous one
+        let mut previous = colors.iter().flatten().next().cloned().unwrap_or([0.0; 3]);
+        let colors = colors
+            .into_iter()
+            .map(|color| {
+                previous = color.unwrap_or(previous);
+                previous
+            })
+            .collect();
+
+        Ok(Self {
+            times: sample_times(config, duration),
+            colors,
+        })
+    }
+
+    // Read a fingerprint which was written by `write_to`
+    fn read(filename: &str) -> Result<Self, String> {
+        let content = match fs::read_to_string(filename) {
+            Ok(content) => content,
+            Err(e) => return Err(format!("Could not read '{}': {}", filename, e)),
+        };
+
+        let mut times = vec![];
+        let mut colors = vec![];
+        for (i, line) in content.lines().enumerate().skip(1) {
+            let values: Vec<f32> = line
+                .split(',')
+                .map(|value| value.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("Line {} of '{}' is not valid.", i + 1, filename))?;
+            if values.len() != 4 {
+                return Err(format!("Line {} of '{}' is not valid.", i + 1, filename));
+            }
+            times.push(values[0]);
+            colors.push([values[1], values[2], values[3]]);
+        }
+
+        if times.len() < 2 {
+            return Err(format!("'{}' is not a fingerprint.", filename));
+        }
+        Ok(Self { times, colors })
+    }
+
+    // Write the fingerprint to `filename` as CSV, with one line per sample
+    pub fn write_to(&self, filename: &str) -> Result<(), String> {
+        let mut content = String::from("time,red,green,blue\n");
+        for (time, color) in self.times.iter().zip(&self.colors) {
+            content.push_str(&format!(
+                "{:.3},{:.2},{:.2},{:.2}\n",
+                time, color[0], color[1], color[2]
+            ));
+        }
+
+        let mut file = match File::create(filename) {
+            Ok(file) => file,
+            Err(e) => {
+                return Err(format!("Could not create '{}': {})", &filename, e));
+            }
+        };
+
+        match file.write_all(content.as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+        }
+    }
+
+    // Seconds between two samples
+    fn step(&self) -> f32 {
+        let last = self.times.len() - 1;
+        (self.times[last] - self.times[0]) / last as f32
+    }
+}
+
+// Find the alignment of fingerprint `b` to fingerprint `a` which makes them most similar, trying
+// different offsets and speeds. Returns `None` if they can't be compared, because one of them
+// doesn't change over time.
+pub fn compare(a: &Fingerprint, b: &Fingerprint) -> Option<Alignment> {
+    let (a_values, b_values) = (&a.colors, &b.colors);
+    let (a_step, b_step) = (a.step(), b.step());
+    if a_step <= 0.0 || b_step <= 0.0 {
+        return None;
+    }
+
+    // Positions are relative to the first sample while searching
+    let a_duration = a_step * a_values.len() as f32;
+    let b_duration = b_step * b_values.len() as f32;
+    let min_overlap = MIN_OVERLAP * a_duration.min(b_duration);
+
+    let search = |scales: &mut dyn Iterator<Item = f32>| {
+        let mut best: Option<(f32, f32, f32)> = None;
+        for scale in scales {
+            // Offsets of b's first sample in a, for which enough of them overlap
+            let from = (min_overlap - scale * b_duration) / a_step;
+            let to = (a_duration - min_overlap) / a_step;
+            for k in from.ceil() as i32..=to.floor() as i32 {
+                let offset = k as f32 * a_step;
+                let value = similarity(a_values, b_values, a_step, b_step, scale, offset);
+                match (value, best) {
+                    (Some(value), Some((best_value, _, _))) if value <= best_value => {}
+                    (Some(value), _) => best = Some((value, scale, offset)),
+                    (None, _) => {}
+                }
+            }
+        }
+        best
+    };
+
+    let limit = (MAX_SCALE.ln() / COARSE_SCALE_STEP.ln()).round() as i32;
+    let (_, coarse_scale, _) = search(&mut (-limit..=limit).map(|i| COARSE_SCALE_STEP.powi(i)))?;
+    let limit = (COARSE_SCALE_STEP.ln() / FINE_SCALE_STEP.ln()).round() as i32;
+    let (similarity, scale, offset) =
+        search(&mut (-limit..=limit).map(|i| coarse_scale * FINE_SCALE_STEP.powi(i)))?;
+
+    Some(Alignment {
+        similarity: similarity.max(0.0).min(1.0),
+        scale,
+        shift: a.times[0] + offset - scale * b.times[0],
+    })
+}
+
+// Returns how similar the colors `a` and `b` are where they overlap, if the n-th sample of `b` is
+// placed at `scale * n * b_step + offset` seconds into `a`. This is the correlation of each color
+// channel, averaged, which ignores differences in brightness or grading. Returns `None` if nothing
+// in the overlap changes.
+fn similarity(
+    a: &[[f32; 3]],
+    b: &[[f32; 3]],
+    a_step: f32,
+    b_step: f32,
+    scale: f32,
+    offset: f32,
+) -> Option<f32> {
+    let pairs: Vec<(&[f32; 3], &[f32; 3])> = b
+        .iter()
+        .enumerate()
+        .filter_map(|(j, b_value)| {
+            let i = ((scale * j as f32 * b_step + offset) / a_step).round();
+            if i >= 0.0 && i < a.len() as f32 {
+                Some((&a[i as usize], b_value))
+            } else {
+                None
+            }
+        })
+        .collect();
+    let count = pairs.len() as f32;
+
+    let correlations: Vec<f32> = (0..3)
+        .filter_map(|channel| {
+            let a_mean = pairs.iter().map(|(a, _)| a[channel]).sum::<f32>() / count;
+            let b_mean = pairs.iter().map(|(_, b)| b[channel]).sum::<f32>() / count;
+            let (mut product, mut a_variance, mut b_variance) = (0.0, 0.0, 0.0);
+            for (a, b) in &pairs {
+                let (a, b) = (a[channel] - a_mean, b[channel] - b_mean);
+                product += a * b;
+                a_variance += a * a;
+                b_variance += b * b;
+            }
+
+            // Channels which don't change can't be correlated
+            if a_variance > 1e-3 && b_variance > 1e-3 {
+                Some(product / (a_variance * b_variance).sqrt())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    if correlations.is_empty() {
+        None
+    } else {
+        Some(correlations.iter().sum::<f32>() / correlations.len() as f32)
+    }
+}
+
+// Returns the positions of the samples, which are the centers of the timeline's columns
+fn sample_times(config: &Config, duration: f32) -> Vec<f32> {
+    let offset = crate::timestamp_offset(config);
+    (0..config.width)
+        .map(|x| offset + duration * (x as f32 + 0.5) / config.width as f32)
+        .collect()
+}
+
+// Returns the average color of `frame`, as RGB
+fn average(frame: &frame::Frame) -> [f32; 3] {
+    let map = frame
+        .buffer
+        .map_readable()
+        .expect("Could not get readable map of frame for fingerprint");
+
+    let mut sums = [0.0; 3];
+    let mut count = 0;
+    for pixel in map.as_slice().chunks_exact(4) {
+        // Frames are stored as BGRx
+        sums[0] += f32::from(pixel[2]);
+        sums[1] += f32::from(pixel[1]);
+        sums[2] += f32::from(pixel[0]);
+        count += 1;
+    }
+
+    let count = count.max(1) as f32;
+    [sums[0] / count, sums[1] / count, sums[2] / count]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fingerprint::{compare, Fingerprint};
+
+    // A fingerprint of `count` samples, `step` seconds apart, whose colors follow `f` over time
+    fn fingerprint(count: usize, step: f32, f: impl Fn(f32) -> [f32; 3]) -> Fingerprint {
+        let times: Vec<f32> = (0..count).map(|i| i as f32 * step).collect();
+        let colors = times.iter().map(|&t| f(t)).collect();
+        Fingerprint { times, colors }
+    }
+
+    fn signal(t: f32) -> [f32; 3] {
+        let value = 128.0 + 100.0 * (t * 0.7).sin() * (t * 0.13).cos();
+        [value, 255.0 - value, 128.0 + 50.0 * (t * 0.31).sin()]
+    }
+
+    #[test]
+    fn test_compare() {
+        // An excerpt from 20 to 60 seconds
+        let a = fingerprint(400, 0.25, signal);
+        let b = fingerprint(200, 0.2, |t| signal(t + 20.0));
+        let alignment = compare(&a, &b).expect("Could not compare fingerprints");
+        assert!(alignment.similarity > 0.95);
+        assert!((alignment.scale - 1.0).abs() < 0.005);
+        assert!((alignment.shift - 20.0).abs() < 0.5);
+
+        // A version which plays 4% faster
+        let b = fingerprint(200, 0.25, |t| signal(t * 1.04));
+        let alignment = compare(&a, &b).expect("Could not compare fingerprints, part 2");
+        assert!((alignment.scale - 1.04).abs() < 0.005);
+        assert!(alignment.shift.abs() < 0.5);
+
+        // Nothing to compare if nothing changes
+        let flat = fingerprint(100, 0.25, |_| [50.0; 3]);
+        assert!(compare(&a, &flat).is_none());
+    }
+}
@@ -0,0 +1,291 @@
+use crate::frame;
+use crate::json;
+use crate::qc::{self, Span};
+use crate::source;
+use crate::Config;
+use std::fs::File;
+use std::io::Write;
+
+// Frames per second which are analyzed. Flashing becomes harmful from 3 flashes per second, and
+// each flash consists of two transitions, which all need to be seen.
+pub const FRAMERATE: f32 = 25.0;
+
+// A change of the relative luminance by at least this much is a transition, if the darker of the
+// two frames is below `DARK_LUMINANCE`
+const LUMINANCE_CHANGE: f32 = 0.1;
+const DARK_LUMINANCE: f32 = 0.8;
+
+// Frames whose red makes up at least this share of their color are saturated red, and a change
+// of their red value by more than `RED_CHANGE` is a transition
+const RED_RATIO: f32 = 0.8;
+const RED_CHANGE: f32 = 20.0;
+
+// Flashing is harmful if there are more than three flashes, so more than six transitions, within
+// one second
+const MAX_TRANSITIONS: usize = 6;
+const PERIOD: f32 = 1.0;
+
+// Colors of the markers on the timeline, as RGB
+const LUMINANCE_MARKER: [u8; 3] = [255, 220, 0];
+const RED_MARKER: [u8; 3] = [230, 30, 30];
+
+// Finds the transitions of a value which swings back and forth between extremes
+#[derive(Default)]
+struct Transitions {
+    // The most recent extreme of the value
+    extreme: Option<f32>,
+    // Whether the last transition went up, or `None` if there was none yet
+    rising: Option<bool>,
+    // Positions of the transitions
+    positions: Vec<f32>,
+}
+
+impl Transitions {
+    // Continue with the `value` at `pts`. A change from the last extreme counts as a transition if
+    // it is `significant`.
+    fn update(&mut self, pts: f32, value: f32, significant: impl Fn(f32, f32) -> bool) {
+        let extreme = match self.extreme {
+            Some(extreme) => extreme,
+            None => {
+                self.extreme = Some(value);
+                return;
+            }
+        };
+
+        // Moving further in the direction of the last transition only shifts the extreme
+        let rising = value > extreme;
+        if self.rising == Some(rising) {
+            self.extreme = Some(value);
+        } else if significant(extreme, value) {
+            self.positions.push(pts);
+            self.rising = Some(rising);
+            self.extreme = Some(value);
+        }
+    }
+
+    // Returns the spans in which there are more than `MAX_TRANSITIONS` within `PERIOD` seconds
+    fn harmful(&self) -> Vec<Span> {
+        let mut spans: Vec<Span> = vec![];
+
+        for window in self.positions.windows(MAX_TRANSITIONS + 1) {
+            let (start, end) = (window[0], window[MAX_TRANSITIONS]);
+            if end - start >= PERIOD {
+                continue;
+            }
+
+            match spans.last_mut() {
+                Some(last) if start <= last.end => last.end = end,
+                _ => spans.push(Span { start, end }),
+            }
+        }
+
+        spans
+    }
+}
+
+// Finds flashing which might cause seizures in people with photosensitive epilepsy, following the
+// common broadcast guidelines: general flashes, which are changes of the luminance, and red
+// flashes, which are changes to or from saturated red. Frames have to be added in order.
+pub struct Analyzer {
+    luminance: Transitions,
+    red: Transitions,
+    // Linear light of each sRGB value
+    linear: Vec<f32>,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        let linear = (0..256)
+            .map(|value| {
+                let value = value as f32 / 255.0;
+                if value <= 0.040_45 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            })
+            .collect();
+
+        Self {
+            luminance: Transitions::default(),
+            red: Transitions::default(),
+            linear,
+        }
+    }
+}
+
+impl Analyzer {
+    // Measure `frame`, and compare it to the previous ones. The measurements are averaged over
+    // the whole frame, so flashing in small parts of it is underestimated.
+    pub fn add(&mut self, frame: &frame::Frame) {
+        let pts = frame
+            .pts
+            .expect("Could not get PTS of frame for flash analysis");
+        let (luminance, red) = measure(frame, &self.linear);
+
+        self.luminance.update(pts, luminance, |a: f32, b: f32| {
+            (a - b).abs() >= LUMINANCE_CHANGE && a.min(b) < DARK_LUMINANCE
+        });
+        self.red
+            .update(pts, red, |a: f32, b: f32| (a - b).abs() > RED_CHANGE);
+    }
+}
+
+// The spans of a video with harmful flashing
+pub struct Report {
+    pub luminance: Vec<Span>,
+    pub red: Vec<Span>,
+}
+
+impl Report {
+    pub fn new(analyzer: &Analyzer) -> Self {
+        Self {
+            luminance: analyzer.luminance.harmful(),
+            red: analyzer.red.harmful(),
+        }
+    }
+}
+
+// Returns the relative luminance of `frame`, from 0 to 1, and its red value, which is 0 unless
+// the frame is saturated red. Both are calculated from linear light, as defined by WCAG 2, which
+// `linear` maps the sRGB values to.
+fn measure(frame: &frame::Frame, linear: &[f32]) -> (f32, f32) {
+    let map = frame
+        .buffer
+        .map_readable()
+        .expect("Could not get readable map of buffer for flash analysis");
+    let mut sums = [0.0; 3];
+    let mut count = 0;
+    for pixel in map.as_slice().chunks_exact(4) {
+        // Frames are stored as BGRx
+        sums[0] += linear[pixel[2] as usize];
+        sums[1] += linear[pixel[1] as usize];
+        sums[2] += linear[pixel[0] as usize];
+        count += 1;
+    }
+    let count = count.max(1) as f32;
+    let (r, g, b) = (sums[0] / count, sums[1] / count, sums[2] / count);
+
+    (0.2126 * r + 0.7152 * g + 0.0722 * b, red_value(r, g, b))
+}
+
+// Returns the red value of a color in linear light, which is 0 unless the color is saturated red
+fn red_value(r: f32, g: f32, b: f32) -> f32 {
+    if r + g + b > 0.0 && r / (r + g + b) >= RED_RATIO {
+        ((r - g - b) * 320.0).max(0.0)
+    } else {
+        0.0
+    }
+}
+
+// Write the `report` to `filename` as JSON. Positions are offset like all other timestamps.
+pub fn write_to(
+    filename: &str,
+    config: &Config,
+    source: &source::VideoSource,
+    report: &Report,
+) -> Result<(), String> {
+    let offset = crate::timestamp_offset(config) - source.start;
+    let content = json::object(
+        &[
+            ("input", json::string(&config.input_filename)),
+            ("start", crate::timestamp_offset(config).to_string()),
+            ("duration", source.duration.to_string()),
+            ("luminance", qc::spans(&report.luminance, offset)),
+            ("red", qc::spans(&report.red, offset)),
+        ],
+        0,
+    );
+
+    let mut file = match File::create(filename) {
+        Ok(file) => file,
+        Err(e) => {
+            return Err(format!("Could not create '{}': {})", &filename, e));
+        }
+    };
+
+    match writeln!(&mut file, "{}", content) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+    }
+}
+
+// Mark the columns of the visual `timeline` which overlap harmful flashing with a strip along
+// its top edge, in red for red flashes, and in yellow for general ones
+pub fn draw_markers(
+    timeline: &mut frame::Frame,
+    config: &Config,
+    source: &source::VideoSource,
+    report: &Report,
+) {
+    let buffer = timeline
+        .buffer
+        .get_mut()
+        .expect("Could not get mutable buffer for flash markers");
+    let mut data = buffer
+        .map_writable()
+        .expect("Could not get writable map of buffer for flash markers");
+
+    let strip_height = (config.height / 8).max(2).min(config.height);
+    for x in 0..config.width {
+        let from = source.start + source.duration * x as f32 / config.width as f32;
+        let to = source.start + source.duration * (x + 1) as f32 / config.width as f32;
+        let overlaps = |spans: &[Span]| spans.iter().any(|span| span.start < to && span.end > from);
+
+        let color = if overlaps(&report.red) {
+            RED_MARKER
+        } else if overlaps(&report.luminance) {
+            LUMINANCE_MARKER
+        } else {
+            continue;
+        };
+
+        for y in 0..strip_height {
+            let i = (y * config.width + x) * 4;
+            // Frames are stored as BGRx
+            data[i] = color[2];
+            data[i + 1] = color[1];
+            data[i + 2] = color[0];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flash::Transitions;
+
+    fn transitions(values: &[f32], fps: f32) -> Transitions {
+        let mut transitions = Transitions::default();
+        for (i, value) in values.iter().enumerate() {
+            transitions.update(i as f32 / fps, *value, |a: f32, b: f32| {
+                (a - b).abs() >= 0.1
+            });
+        }
+        transitions
+    }
+
+    #[test]
+    fn test_transitions() {
+        // A slow rise is a single transition, and small wobbles are none
+        let rise = transitions(&[0.0, 0.05, 0.1, 0.15, 0.2, 0.25, 0.2, 0.22], 25.0);
+        assert_eq!(rise.positions, vec![0.08]);
+
+        // Switching between dark and bright frames about eight times per second is harmful
+        let flashing: Vec<f32> = (0..50).map(|i| ((i / 3) % 2) as f32).collect();
+        let spans = transitions(&flashing, 25.0).harmful();
+        assert_eq!(spans.len(), 1);
+        assert_eq!((spans[0].start, spans[0].end), (0.12, 1.92));
+
+        // Twice per second is not
+        let flashing: Vec<f32> = (0..50).map(|i| ((i / 12) % 2) as f32).collect();
+        assert!(transitions(&flashing, 25.0).harmful().is_empty());
+    }
+
+    #[test]
+    fn test_red_value() {
+        use crate::flash::red_value;
+        assert_eq!(red_value(0.0, 0.0, 0.0), 0.0);
+        assert_eq!(red_value(0.5, 0.5, 0.5), 0.0);
+        assert!((red_value(0.5, 0.0, 0.0) - 160.0).abs() < 1e-3);
+    }
+}
@@ -3,6 +3,7 @@ extern crate gstreamer_app as gst_app;
 
 use crate::frame::gst::prelude::*;
 use std::fs::File;
+use std::io::Write;
 
 // Holds a GStreamer Buffer, and knows its size and (optionally) its presentation timestamp in
 // seconds
@@ -266,17 +267,125 @@ impl Frame {
         }
     }
 
-    // Write frame to `filename` as a JPEG using GStreamer
-    pub fn write_to(&self, filename: &str, quality: i32) -> Result<bool, String> {
+    // Returns the luma of each pixel, row by row, using the BT.601 weights
+    pub fn luma(&self) -> Vec<f32> {
+        let map = self
+            .buffer
+            .map_readable()
+            .expect("Could not get readable map for luma");
+
+        map.as_slice()
+            .chunks_exact(4)
+            .map(|pixel| {
+                0.114 * f32::from(pixel[0])
+                    + 0.587 * f32::from(pixel[1])
+                    + 0.299 * f32::from(pixel[2])
+            })
+            .collect()
+    }
+
+    // Average the frame in `count` horizontal bands of equal height, and render the results as
+    // blocks into a frame which is 1 pixel wide and `height` pixels high. `count` must not be
+    // larger than the frame's height.
+    pub fn bands(&self, count: usize, height: usize) -> Self {
+        let mut frame = Self::new(1, height);
+
         {
-            match File::create(&filename) {
-                Ok(file) => file,
-                Err(e) => {
-                    return Err(format!("Could not create '{}': {})", &filename, e));
+            let buffer = frame
+                .buffer
+                .get_mut()
+                .expect("Could not get mutable buffer for bands");
+            let mut data = buffer
+                .map_writable()
+                .expect("Could not get writable map of buffer for bands");
+
+            let map = self
+                .buffer
+                .map_readable()
+                .expect("Could not get readable map of buffer for bands");
+            let indata = map.as_slice();
+
+            let mut colors = vec![[0u8; 3]; count];
+
+            for (band, color) in colors.iter_mut().enumerate() {
+                let mut b: usize = 0;
+                let mut g: usize = 0;
+                let mut r: usize = 0;
+
+                let from = self.height * band / count;
+                let to = self.height * (band + 1) / count;
+
+                for y in from..to {
+                    for x in 0..self.width {
+                        b += indata[self.width * y * 4 + 4 * x] as usize;
+                        g += indata[self.width * y * 4 + 4 * x + 1] as usize;
+                        r += indata[self.width * y * 4 + 4 * x + 2] as usize;
+                    }
                 }
-            };
+
+                let pixels = (to - from) * self.width;
+                *color = [(b / pixels) as u8, (g / pixels) as u8, (r / pixels) as u8];
+            }
+
+            for y in 0..height {
+                let color = colors[y * count / height];
+
+                data[y * 4] = color[0];
+                data[y * 4 + 1] = color[1];
+                data[y * 4 + 2] = color[2];
+                data[y * 4 + 3] = 255;
+            }
         }
 
+        frame
+    }
+
+    // Return a new frame containing the `width`*`height` region of `self` starting at x/y
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        let mut frame = Self::new(width, height);
+
+        {
+            let mut data = frame
+                .buffer
+                .get_mut()
+                .expect("Could not get mutable buffer for cropping")
+                .map_writable()
+                .expect("Could not get writable map for cropping");
+
+            let map = self
+                .buffer
+                .map_readable()
+                .expect("Could not get readable map for cropping");
+            let indata = map.as_slice();
+
+            for row in 0..height {
+                let from = ((row + y) * self.width + x) * 4;
+                let to = row * width * 4;
+                data[to..to + width * 4].copy_from_slice(&indata[from..from + width * 4]);
+            }
+        }
+
+        frame.pts = self.pts;
+        frame
+    }
+
+    // Write frame to `filename` as a JPEG using GStreamer
+    pub fn write_to(&self, filename: &str, quality: i32) -> Result<bool, String> {
+        let mut file = match File::create(&filename) {
+            Ok(file) => file,
+            Err(e) => {
+                return Err(format!("Could not create '{}': {})", &filename, e));
+            }
+        };
+
+        match file.write_all(&self.to_jpeg(quality)) {
+            Ok(_) => Ok(true),
+            Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+        }
+    }
+
+    // Encode frame as a JPEG using GStreamer, and return the encoded bytes
+    pub fn to_jpeg(&self, quality: i32) -> Vec<u8> {
         let src =
             gst::ElementFactory::make("appsrc", None).expect("Could not create appsrc for writing");
 
@@ -301,17 +410,14 @@ impl Frame {
         jpegenc
             .set_property("quality", &quality)
             .expect("Could not create quality element");
-        let filesink =
-            gst::ElementFactory::make("filesink", None).expect("Could not create filesink");
-        filesink
-            .set_property("location", &filename)
-            .expect("Could not set property on filesink");
+        let sink = gst::ElementFactory::make("appsink", None)
+            .expect("Could not create appsink for writing");
 
         let pipeline = gst::Pipeline::new(None);
         pipeline
-            .add_many(&[&src, &capsfilter, &jpegenc, &filesink])
+            .add_many(&[&src, &capsfilter, &jpegenc, &sink])
             .expect("Could not create writing pipeline");
-        gst::Element::link_many(&[&src, &capsfilter, &jpegenc, &filesink])
+        gst::Element::link_many(&[&src, &capsfilter, &jpegenc, &sink])
             .expect("Could not link writing pipeline");
 
         let appsrc = src
@@ -321,6 +427,14 @@ impl Frame {
         appsrc.set_property_format(gst::Format::Time);
         appsrc.set_property_block(true);
 
+        let appsink = sink
+            .clone()
+            .dynamic_cast::<gst_app::AppSink>()
+            .expect("Sink element is expected to be an appsink!");
+        appsink
+            .set_property("sync", &false)
+            .expect("Could not set appsink property for writing");
+
         pipeline
             .set_state(gst::State::Playing)
             .into_result()
@@ -340,25 +454,23 @@ impl Frame {
             .into_result()
             .expect("Could not make EOS into result for writing");
 
-        let bus = pipeline.get_bus().expect("Could not get bus for writing");
+        let sample = appsink
+            .pull_sample()
+            .expect("Could not pull sample from writing pipeline");
 
-        loop {
-            match bus.timed_pop(gst::CLOCK_TIME_NONE) {
-                None => {}
-                Some(msg) => match msg.view() {
-                    gst::MessageView::Eos(_) => {
-                        break;
-                    }
-                    _ => {}
-                },
-            }
-        }
+        let jpeg = sample
+            .get_buffer()
+            .expect("Could not get buffer from sample for writing")
+            .map_readable()
+            .expect("Could not get readable map of encoded buffer")
+            .as_slice()
+            .to_vec();
 
         pipeline
             .set_state(gst::State::Null)
             .into_result()
             .expect("Could not stop writing pipeline");
 
-        Ok(true)
+        jpeg
     }
 }
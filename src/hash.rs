@@ -0,0 +1,78 @@
+use crate::frame;
+
+// Size of the image the hash is computed from. Each row yields one bit less than it has pixels,
+// so that the hash has 64 bits.
+const WIDTH: usize = 9;
+const HEIGHT: usize = 8;
+
+// Returns the difference hash (dHash) of `frame`: it is shrunk to 9*8 pixels of luma, and each bit
+// tells whether a pixel is brighter than its right neighbour. Frames which look alike have hashes
+// which differ in only a few bits, regardless of their size, brightness or compression.
+pub fn dhash(frame: &frame::Frame) -> u64 {
+    difference_hash(&frame.luma(), frame.width, frame.height)
+}
+
+// Returns in how many bits two hashes differ
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// Render `hash` as 16 hexadecimal digits
+pub fn hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+// Returns the difference hash of a `width`*`height` image of `luma` values
+fn difference_hash(luma: &[f32], width: usize, height: usize) -> u64 {
+    if width == 0 || height == 0 {
+        return 0;
+    }
+
+    // Average the pixels which fall into each cell of the small image
+    let range = |i: usize, cells: usize, size: usize| {
+        let from = i * size / cells;
+        from..((i + 1) * size / cells).max(from + 1)
+    };
+    let mut cells = [[0.0; WIDTH]; HEIGHT];
+    for (cy, row) in cells.iter_mut().enumerate() {
+        for (cx, cell) in row.iter_mut().enumerate() {
+            let (xs, ys) = (range(cx, WIDTH, width), range(cy, HEIGHT, height));
+            let count = (xs.len() * ys.len()) as f32;
+            let sum: f32 = ys
+                .flat_map(|y| xs.clone().map(move |x| luma[y * width + x]))
+                .sum();
+            *cell = sum / count;
+        }
+    }
+
+    let mut hash = 0;
+    for row in cells.iter() {
+        for x in 0..WIDTH - 1 {
+            hash = (hash << 1) | u64::from(row[x] > row[x + 1]);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::{difference_hash, distance, hex};
+
+    #[test]
+    fn test_difference_hash() {
+        // Brightness increasing to the right, or to the left
+        let rising: Vec<f32> = (0..32 * 24).map(|i| (i % 32) as f32 * 8.0).collect();
+        let falling: Vec<f32> = rising.iter().map(|y| 255.0 - y).collect();
+        assert_eq!(difference_hash(&rising, 32, 24), 0);
+        assert_eq!(difference_hash(&falling, 32, 24), u64::max_value());
+
+        // A brighter copy looks the same, and so does a smaller one
+        let brighter: Vec<f32> = falling.iter().map(|y| y + 20.0).collect();
+        assert_eq!(difference_hash(&brighter, 32, 24), u64::max_value());
+        let small: Vec<f32> = (0..9 * 8).map(|i| 255.0 - (i % 9) as f32 * 30.0).collect();
+        assert_eq!(difference_hash(&small, 9, 8), u64::max_value());
+
+        assert_eq!(distance(0, u64::max_value()), 64);
+        assert_eq!(hex(0xab), "00000000000000ab");
+    }
+}
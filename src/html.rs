@@ -0,0 +1,259 @@
+use crate::json;
+use crate::source;
+use crate::Config;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+// Styles of the preview page. The preview box is positioned by the script.
+const STYLE: &str = "
+body { margin: 2em auto; max-width: 1000px; font-family: sans-serif; background: #222; color: #eee; }
+video { width: 100%; background: black; }
+#timeline { position: relative; margin-top: 1em; cursor: pointer; }
+#timeline img { display: block; width: 100%; height: 50px; }
+#cursor { position: absolute; top: 0; bottom: 0; width: 2px; background: white; pointer-events: none; }
+#preview { position: absolute; bottom: 60px; display: none; border: 2px solid white; pointer-events: none; }
+#time { text-align: center; }
+";
+
+// Seek on click, show the thumbnail of the hovered time range, and follow the playback position.
+// The timeline covers `duration` seconds of the video, starting at `start`.
+const SCRIPT: &str = "
+var video = document.getElementById('video');
+var timeline = document.getElementById('timeline');
+var cursor = document.getElementById('cursor');
+var preview = document.getElementById('preview');
+var time = document.getElementById('time');
+
+function format(seconds) {
+  var minutes = Math.floor(seconds / 60);
+  seconds = Math.floor(seconds % 60);
+  return minutes + ':' + (seconds < 10 ? '0' : '') + seconds;
+}
+
+function fraction(event) {
+  var rect = timeline.getBoundingClientRect();
+  return Math.min(Math.max((event.clientX - rect.left) / rect.width, 0), 1);
+}
+
+timeline.addEventListener('click', function(event) {
+  video.currentTime = start + fraction(event) * duration;
+});
+
+timeline.addEventListener('mousemove', function(event) {
+  var f = fraction(event);
+  time.textContent = format(start + f * duration);
+  if (cues.length == 0) {
+    return;
+  }
+  var cue = cues[Math.min(Math.floor(f * cues.length), cues.length - 1)];
+  preview.style.display = 'block';
+  preview.style.width = cue[5] + 'px';
+  preview.style.height = cue[6] + 'px';
+  preview.style.background = 'url(\"' + cue[2] + '\") -' + cue[3] + 'px -' + cue[4] + 'px';
+  var left = f * timeline.clientWidth - cue[5] / 2;
+  preview.style.left = Math.min(Math.max(left, 0), timeline.clientWidth - cue[5]) + 'px';
+});
+
+timeline.addEventListener('mouseleave', function() {
+  preview.style.display = 'none';
+  time.textContent = '';
+});
+
+video.addEventListener('timeupdate', function() {
+  var f = Math.min(Math.max((video.currentTime - start) / duration, 0), 1);
+  cursor.style.left = (100 * f) + '%';
+});
+";
+
+// Write a static HTML page to `filename`, which plays the input file and uses the timeline as a
+// seek bar. If thumbnails were generated, hovering over the timeline shows them as previews.
+//
+// All files are referenced relative to the page, so it can be moved together with them.
+pub fn write_to(
+    filename: &str,
+    config: &Config,
+    start: f32,
+    duration: f32,
+    tiles: &[usize],
+) -> Result<(), String> {
+    let base = base_directory(&filename)?;
+
+    // The input might also be a list, which contains a single file
+    let input_filename = &config.input_filenames[0];
+    let video = if source::is_uri(input_filename) {
+        input_filename.clone()
+    } else {
+        relative_url(&base, input_filename)?
+    };
+    let timeline = match config.timeline_filename {
+        Some(ref timeline_filename) => relative_url(&base, timeline_filename)?,
+        None => return Err(String::from("The preview page requires a timeline")),
+    };
+
+    // Each cue is [from, to, grid, x, y, w, h], with times in seconds
+    let mut cues = vec![];
+    if config.vtt_filename.is_some() {
+        for (i, &tile) in tiles.iter().enumerate() {
+            let (from, to) = crate::cue_range(i, &config, duration);
+            let (file, x, y) = crate::grid_position(tile, &config);
+            let grid = relative_url(&base, &crate::grid_filename(file, &config))?;
+            cues.push(format!(
+                "[{}, {}, {}, {}, {}, {}, {}]",
+                from as f32 / 1000.0,
+                to as f32 / 1000.0,
+                script_string(&grid),
+                x,
+                y,
+                config.thumbnail_width,
+                config.thumbnail_height
+            ));
+        }
+    }
+
+    let title = Path::new(input_filename)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(input_filename);
+
+    let page = format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>{style}</style>
+</head>
+<body>
+<video id=\"video\" src=\"{video}\" controls></video>
+<div id=\"timeline\">
+<img src=\"{timeline}\" alt=\"Visual timeline\">
+<div id=\"cursor\"></div>
+<div id=\"preview\"></div>
+</div>
+<div id=\"time\"></div>
+<script>
+var start = {start};
+var duration = {duration};
+var cues = [{cues}];
+{script}</script>
+</body>
+</html>
+",
+        title = escape(title),
+        style = STYLE,
+        video = escape(&video),
+        timeline = escape(&timeline),
+        start = start,
+        duration = duration,
+        cues = cues.join(",\n"),
+        script = SCRIPT
+    );
+
+    let mut file = match File::create(&filename) {
+        Ok(file) => file,
+        Err(e) => {
+            return Err(format!("Could not create '{}': {})", &filename, e));
+        }
+    };
+
+    match file.write_all(page.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+    }
+}
+
+// Returns the absolute path of the directory `filename` will be written to
+fn base_directory(filename: &str) -> Result<PathBuf, String> {
+    let parent = match Path::new(filename).parent() {
+        Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    match fs::canonicalize(&parent) {
+        Ok(path) => Ok(path),
+        Err(e) => Err(format!("Could not open directory of '{}': {}", filename, e)),
+    }
+}
+
+// Returns a URL which references the existing file `target`, relative to the directory `base`
+fn relative_url(base: &Path, target: &str) -> Result<String, String> {
+    let target = match fs::canonicalize(target) {
+        Ok(path) => path,
+        Err(e) => {
+            return Err(format!("Could not open '{}': {}", target, e));
+        }
+    };
+
+    let base_components: Vec<Component> = base.components().collect();
+    let target_components: Vec<Component> = target.components().collect();
+
+    // Skip the common prefix, then go up for every remaining directory in `base`
+    let common = base_components
+        .iter()
+        .zip(target_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut parts = vec![String::from(".."); base_components.len() - common];
+    for component in &target_components[common..] {
+        parts.push(component.as_os_str().to_string_lossy().into_owned());
+    }
+
+    Ok(parts
+        .iter()
+        .map(|part| encode(part))
+        .collect::<Vec<String>>()
+        .join("/"))
+}
+
+// Percent-encode characters which have a special meaning in URLs
+fn encode(part: &str) -> String {
+    let mut result = String::new();
+    for c in part.chars() {
+        match c {
+            ' ' | '"' | '#' | '%' | '?' | '\'' | '<' | '>' | '\\' => {
+                result.push_str(&format!("%{:02X}", c as u32))
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+// Quote `text` as a string in the script. On top of the escapes of JSON, this keeps `</script>`
+// from ending the script, and escapes the line and paragraph separators, which JavaScript didn't
+// allow in strings before ES2019.
+fn script_string(text: &str) -> String {
+    json::string(text)
+        .replace("</", "<\\/")
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+}
+
+// Escape characters which have a special meaning in HTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_encode() {
+        use crate::html::encode;
+        assert_eq!(encode("my video #1.mp4"), "my%20video%20%231.mp4");
+    }
+
+    #[test]
+    fn test_script_string() {
+        use crate::html::script_string;
+        assert_eq!(
+            script_string("a\"</script>\u{2028}"),
+            "\"a\\\"<\\/script>\\u2028\""
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// Helpers for writing JSON files by hand
+
+// Quote `s` as a JSON string, escaping all characters which need it
+pub fn string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// Render a JSON object from a list of keys and already rendered values, indented by `indent`
+// levels
+pub fn object(fields: &[(&str, String)], indent: usize) -> String {
+    if fields.is_empty() {
+        return String::from("{}");
+    }
+
+    let inner = "  ".repeat(indent + 1);
+    let fields = fields
+        .iter()
+        .map(|(key, value)| format!("{}{}: {}", inner, string(key), value))
+        .collect::<Vec<String>>()
+        .join(",\n");
+    format!("{{\n{}\n{}}}", fields, "  ".repeat(indent))
+}
+
+// Render a JSON array from a list of already rendered values, indented by `indent` levels
+pub fn array(values: &[String], indent: usize) -> String {
+    if values.is_empty() {
+        return String::from("[]");
+    }
+
+    let inner = "  ".repeat(indent + 1);
+    let values = values
+        .iter()
+        .map(|value| format!("{}{}", inner, value))
+        .collect::<Vec<String>>()
+        .join(",\n");
+    format!("[\n{}\n{}]", values, "  ".repeat(indent))
+}
+
+// Render a number, using `null` for NaN and infinity, which JSON can't represent
+pub fn number(value: f32) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        String::from("null")
+    }
+}
+
+// Render an optional value, using `null` if it's missing
+pub fn optional(value: Option<String>) -> String {
+    value.unwrap_or_else(|| String::from("null"))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_string() {
+        use crate::json::string;
+        assert_eq!(string("a \"b\"\\\n"), "\"a \\\"b\\\"\\\\\\n\"");
+    }
+
+    #[test]
+    fn test_number() {
+        use crate::json::number;
+        assert_eq!(number(0.5), "0.5");
+        assert_eq!(number(std::f32::NAN), "null");
+        assert_eq!(number(std::f32::INFINITY), "null");
+    }
+
+    #[test]
+    fn test_object() {
+        use crate::json::{array, object};
+        assert_eq!(
+            object(
+                &[
+                    ("a", String::from("1")),
+                    ("b", array(&[String::from("2")], 1))
+                ],
+                0
+            ),
+            "{\n  \"a\": 1,\n  \"b\": [\n    2\n  ]\n}"
+        );
+    }
+}
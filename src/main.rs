@@ -4,6 +4,7 @@ extern crate colored;
 
 use clap::AppSettings;
 use clap::Arg;
+use clap::ArgGroup;
 use colored::*;
 use std::cmp;
 use std::fs;
@@ -15,38 +16,193 @@ use std::path::PathBuf;
 use std::process;
 use std::time::{Duration, SystemTime};
 
+mod audio;
+mod bif;
+mod cuts;
+mod fingerprint;
+mod flash;
 mod frame;
+mod hash;
+mod html;
+mod json;
+mod manifest;
+mod palette;
+mod playlist;
+mod qc;
+mod selection;
+mod sequence;
 mod source;
+mod spectrogram;
 
 // Maximum dimensions of a single thumbnail grid
 const MAX_GRID_WIDTH: usize = 1000;
 const MAX_GRID_HEIGHT: usize = 1000;
 
+// Thumbnails are duplicates if their perceptual hashes differ in at most this many bits, and
+// their luma differs by less than this on average. The second check tells apart flat thumbnails
+// of different brightness, whose hashes are all the same.
+const DUPLICATE_DISTANCE: u32 = 4;
+const DUPLICATE_DIFFERENCE: f32 = 3.0;
+
 fn main() {
     // Parse the command line arguments
     let mut config = parse_config();
 
+    if let Some(ref compare_filename) = config.compare_filename {
+        compare(&config, compare_filename);
+    }
+
     // Set source height to the timeline height, or the thumbnail height, whichever is larger.
     let source_height = cmp::max(config.thumbnail_height, config.height);
 
     // Create and initialize VideoSource
-    let mut source =
-        match source::VideoSource::new(&config.input_filename, source_height, config.width) {
-            Ok(source) => source,
-            Err(message) => error(&message),
-        };
+    let options = source_options(&config);
+    let mut source = match source::VideoSource::new(
+        &config.input_filenames,
+        &config.input_edits,
+        source_height,
+        config.width,
+        &options,
+    ) {
+        Ok(source) => source,
+        Err(message) => error(&message),
+    };
+
+    // Inputs without video are shown as a spectrogram, which doesn't make for thumbnails
+    if source.meta.audio_only && config.thumbnails_needed() {
+        error("Thumbnails can't be extracted from an input without video.");
+    }
+    if source.meta.audio_only && config.cuts_needed() {
+        error("Cuts can't be detected in an input without video.");
+    }
+    if source.meta.audio_only && config.qc_filename.is_some() {
+        error("The QC report can't be created for an input without video.");
+    }
+    if source.meta.audio_only && config.flash_filename.is_some() {
+        error("Flashes can't be detected in an input without video.");
+    }
+    if source.meta.audio_only && config.palette_needed() {
+        error("Palettes can't be extracted from an input without video.");
+    }
 
     // Derive thumbnail width and column count from the aspect ratio of the VideoSource
     let aspect_ratio = source.width as f32 / source.height as f32;
-    config.thumbnail_width = (aspect_ratio * config.thumbnail_height as f32) as usize;
+    config.thumbnail_width = cmp::max((aspect_ratio * config.thumbnail_height as f32) as usize, 1);
     let max_image_width = 5000;
     config.thumbnail_columns = max_image_width / config.thumbnail_width;
 
     // The hard part: generate the timeline and the thumbnail grid
-    let (timeline, thumbnail_grids) = generate_timeline_and_thumbnails(&config, &mut source);
+    let mut detector = if config.cuts_needed() {
+        Some(cuts::Detector::new(config.cut_threshold))
+    } else {
+        None
+    };
+    let mut checker = if config.qc_filename.is_some() {
+        Some(qc::Checker::default())
+    } else {
+        None
+    };
+    let mut analyzer = if config.flash_filename.is_some() {
+        Some(flash::Analyzer::default())
+    } else {
+        None
+    };
+    let mut extractor = if config.palette_needed() {
+        Some(palette::Extractor::new(
+            source.start,
+            config.palette_colors,
+            config.palette_interval,
+        ))
+    } else {
+        None
+    };
+    let (mut timeline, thumbnail_grids) = generate_timeline_and_thumbnails(
+        &config,
+        &mut source,
+        &mut detector,
+        &mut checker,
+        &mut analyzer,
+        &mut extractor,
+    );
 
     println!();
 
+    // Without deduplication, every thumbnail has a tile of its own
+    let (thumbnail_grids, tiles) = if config.dedup_thumbnails {
+        let (grids, tiles) = deduplicate_thumbnails(&config, &thumbnail_grids);
+        let unique = tiles.iter().max().map_or(0, |tile| tile + 1);
+        println!(
+            "-> {} of {} thumbnails are duplicates",
+            config.width - unique,
+            config.width
+        );
+        (grids, tiles)
+    } else {
+        (thumbnail_grids, (0..config.width).collect())
+    };
+
+    // The fingerprint is taken before anything is drawn onto the timeline
+    if let Some(ref fingerprint_filename) = config.fingerprint_filename {
+        let fingerprint =
+            fingerprint::Fingerprint::from_timeline(&timeline, &config, source.duration);
+        match fingerprint.write_to(fingerprint_filename) {
+            Ok(_) => {
+                println!("-> fingerprint written to '{}'", fingerprint_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
+    let report = checker.map(|checker| qc::Report::new(checker, &source));
+    if let (Some(report), true) = (&report, config.qc_overlay) {
+        qc::draw_overlay(&mut timeline, &config, &source, report);
+    }
+
+    let flashes = analyzer.map(|analyzer| flash::Report::new(&analyzer));
+    if let (Some(flashes), true) = (&flashes, config.flash_markers) {
+        flash::draw_markers(&mut timeline, &config, &source, flashes);
+    }
+
+    let palettes = extractor.map(|extractor| palette::Report::new(extractor, &source));
+
+    let cuts = detector.map_or(vec![], |detector| detector.cuts);
+    if config.cut_markers {
+        cuts::draw_markers(&mut timeline, &config, &source, &cuts);
+    }
+
+    // Now that the whole audio track was measured, draw it
+    let audio_strip = config.audio.map(|style| {
+        let levels = source
+            .audio
+            .as_ref()
+            .expect("Could not get audio levels from source")
+            .lock()
+            .expect("Could not lock audio levels");
+        if levels.is_empty() {
+            println!("-> the input has no audio track, so the audio strip is empty");
+        }
+        levels.render(
+            style,
+            config.width,
+            config.audio_height,
+            source.start,
+            source.duration,
+        )
+    });
+
+    // Unless the audio strip gets its own file, it is appended below the timeline
+    let timeline = match (&audio_strip, &config.audio_filename) {
+        (Some(audio_strip), None) => {
+            let mut combined = frame::Frame::new(config.width, config.height + config.audio_height);
+            combined.copy(&timeline, 0, 0);
+            combined.copy(audio_strip, 0, config.height);
+            combined
+        }
+        _ => timeline,
+    };
+
     if let Some(ref timeline_filename) = config.timeline_filename {
         // Write resulting timeline to a file
         match timeline.write_to(&timeline_filename, 90) {
@@ -59,9 +215,85 @@ fn main() {
         }
     }
 
+    if let (Some(audio_strip), Some(audio_filename)) = (&audio_strip, &config.audio_filename) {
+        match audio_strip.write_to(audio_filename, 90) {
+            Ok(_) => {
+                println!("-> audio strip written to '{}'", audio_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
+    if let Some(ref cuts_filename) = config.cuts_filename {
+        match cuts::write_to(cuts_filename, &config, &source, &cuts) {
+            Ok(_) => {
+                println!("-> {} cuts written to '{}'", cuts.len(), cuts_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
+    if let (Some(qc_filename), Some(report)) = (&config.qc_filename, &report) {
+        match qc::write_to(qc_filename, &config, &source, report) {
+            Ok(_) => {
+                println!("-> QC report written to '{}'", qc_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
+    if let (Some(flash_filename), Some(flashes)) = (&config.flash_filename, &flashes) {
+        match flash::write_to(flash_filename, &config, &source, flashes) {
+            Ok(_) => {
+                if flashes.luminance.is_empty() && flashes.red.is_empty() {
+                    println!("-> no harmful flashing found");
+                } else {
+                    println!("-> {}: harmful flashing found", "warning".yellow().bold());
+                }
+                println!("-> flash report written to '{}'", flash_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
+    if let (Some(palette_filename), Some(palettes)) = (&config.palette_filename, &palettes) {
+        match palette::write_to(palette_filename, &config, &source, palettes) {
+            Ok(_) => {
+                println!(
+                    "-> palettes of {} segments written to '{}'",
+                    palettes.segments.len(),
+                    palette_filename
+                );
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
+    if let (Some(swatches_filename), Some(palettes)) = (&config.swatches_filename, &palettes) {
+        let swatches = palette::draw_swatches(&config, &source, palettes);
+        match swatches.write_to(swatches_filename, 90) {
+            Ok(_) => {
+                println!("-> palette swatches written to '{}'", swatches_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
     if let Some(ref vtt_filename) = config.vtt_filename {
         // Write the VTT file
-        write_vtt(&config, source.duration);
+        write_vtt(&config, source.duration, &tiles);
         println!("-> VTT written to '{}'", vtt_filename);
 
         print!("-> thumbnail grids written to");
@@ -79,6 +311,56 @@ fn main() {
         }
         println!();
     }
+
+    if let Some(ref bif_filename) = config.bif_filename {
+        // Encode one thumbnail per interval and write them to a BIF file
+        let images = bif_images(&config, &thumbnail_grids, &tiles, source.duration);
+        match bif::write_to(&bif_filename, &images) {
+            Ok(_) => {
+                println!("-> BIF written to '{}'", bif_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
+    if let Some(ref html_filename) = config.html_filename {
+        // Write a preview page which references all of the above
+        match html::write_to(
+            &html_filename,
+            &config,
+            source.start,
+            source.duration,
+            &tiles,
+        ) {
+            Ok(_) => {
+                println!("-> preview page written to '{}'", html_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
+
+    if let Some(ref manifest_filename) = config.manifest_filename {
+        // Describe all of the above in a JSON file
+        match manifest::write_to(
+            &manifest_filename,
+            &config,
+            &source,
+            &thumbnail_grids,
+            &tiles,
+            &cuts,
+        ) {
+            Ok(_) => {
+                println!("-> manifest written to '{}'", manifest_filename);
+            }
+            Err(description) => {
+                error(&description);
+            }
+        }
+    }
 }
 
 // Config objects are used to describe a single Timeline run
@@ -87,6 +369,9 @@ pub struct Config {
     width: usize,
     // Height of visual timeline
     height: usize,
+    // Number of horizontal bands each timeline column is divided into, if the frames' vertical
+    // structure should be summarized in blocks instead of being resampled row by row
+    bands: Option<usize>,
 
     // Width of single thumbnail
     thumbnail_width: usize,
@@ -94,13 +379,119 @@ pub struct Config {
     thumbnail_height: usize,
     // Number of columns in the thumbnail grid
     thumbnail_columns: usize,
+    // Whether each thumbnail is the most representative frame of its interval, instead of the
+    // first one
+    pick_thumbnails: bool,
+    // Whether thumbnails which look the same share a single tile of the thumbnail grids
+    dedup_thumbnails: bool,
 
-    // Name of the input file
+    // Name of the input file, or of the list of input files. Default output names are based on it.
     input_filename: String,
+    // Names of the input files, which are concatenated if there are several
+    input_filenames: Vec<String>,
+    // Which range of each input file is used, if an EDL selects one
+    input_edits: Vec<Option<playlist::Edit>>,
+    // Position in seconds where processing starts
+    start: f32,
+    // Position in seconds where processing ends, or `None` to process until the end
+    end: Option<f32>,
+    // Whether timestamps in the outputs are relative to `start`, instead of to the beginning of
+    // the input file
+    relative_timestamps: bool,
+    // Don't trust the duration the input reports, and decode until the end instead
+    ignore_duration: bool,
+    // Borders to remove from the input frames, or `None` to detect black borders automatically
+    crop: Option<source::Crop>,
+    // How to deinterlace the input frames
+    deinterlace: source::Deinterlace,
+    // Framerate of image sequences, as numerator and denominator
+    framerate: (u32, u32),
+    // Index, identifier or language of the video stream to use, or `None` for the first one
+    stream: Option<String>,
+    // How to visualize the audio track, or `None` to not analyze it
+    audio: Option<audio::Style>,
+    // Height of the audio strip in pixels
+    audio_height: usize,
+    // Name of the file the audio strip will be written to. If this is `None`, it is appended below
+    // the visual timeline.
+    audio_filename: Option<String>,
+    // Name of the file the cuts between shots will be written to, as CSV, EDL or WebVTT chapters
+    cuts_filename: Option<String>,
+    // Histogram difference between two frames, from 0 to 1, from which on they count as a cut
+    cut_threshold: f32,
+    // Whether to mark the cuts on the visual timeline
+    cut_markers: bool,
+    // Name of the file the QC report about black frames, frozen frames and silence will be
+    // written to
+    qc_filename: Option<String>,
+    // Whether to highlight the spans from the QC report on the visual timeline
+    qc_overlay: bool,
+    // Name of the file the report about flashing which might trigger seizures will be written to
+    flash_filename: Option<String>,
+    // Whether to mark harmful flashing on the visual timeline
+    flash_markers: bool,
+    // Name of the file the color palettes will be written to, and of the image showing them
+    palette_filename: Option<String>,
+    swatches_filename: Option<String>,
+    // Number of colors in each palette
+    palette_colors: usize,
+    // Length in seconds of the segments which get a palette of their own, or `None` if the
+    // scenes between cuts do
+    palette_interval: Option<f32>,
+    // Name of the file the fingerprint of the input will be written to
+    fingerprint_filename: Option<String>,
+    // Name of the video or fingerprint the input is compared to. If this is set, nothing else is
+    // done.
+    compare_filename: Option<String>,
     // Name of the file the visual timeline will be written to
     timeline_filename: Option<String>,
     // Name of the file the VTT file will be written to
     vtt_filename: Option<String>,
+    // Name of the file the BIF file will be written to
+    bif_filename: Option<String>,
+    // Seconds between two thumbnails in the BIF file
+    bif_interval: u32,
+    // Name of the file the JSON manifest will be written to
+    manifest_filename: Option<String>,
+    // Name of the file the HTML preview page will be written to
+    html_filename: Option<String>,
+}
+
+impl Config {
+    // Whether the visual timeline needs to be rendered
+    fn timeline_needed(&self) -> bool {
+        self.timeline_filename.is_some() || self.fingerprint_filename.is_some()
+    }
+
+    // Whether any of the requested outputs needs thumbnails
+    fn thumbnails_needed(&self) -> bool {
+        self.vtt_filename.is_some() || self.bif_filename.is_some()
+    }
+
+    // Frames per second the analyses need to look at, if that's more than the timeline needs
+    fn min_framerate(&self) -> Option<f32> {
+        if self.flash_filename.is_some() {
+            Some(flash::FRAMERATE)
+        } else if self.cuts_needed() || self.qc_filename.is_some() {
+            Some(cuts::FRAMERATE)
+        } else {
+            None
+        }
+    }
+
+    // Whether cuts between shots need to be detected. Picking thumbnails avoids frames next to
+    // them, and palettes are extracted per scene, unless they are for fixed intervals.
+    fn cuts_needed(&self) -> bool {
+        self.cuts_filename.is_some()
+            || self.cut_markers
+            || self.pick_thumbnails
+            || (self.palette_needed() && self.palette_interval.is_none())
+    }
+
+    // Whether color palettes need to be extracted
+    fn palette_needed(&self) -> bool {
+        self.palette_filename.is_some() || self.swatches_filename.is_some()
+    }
 }
 
 // Generate a Config from the command line arguments
@@ -114,10 +505,30 @@ fn parse_config() -> Config {
             "--timeline timeline.jpg -w 1000 -h 500",
             "Override size and name of the timeline file.",
         ),
+        (
+            "-h 90 --bands 3",
+            "Show the average colors of the top, middle and bottom third of each frame.",
+        ),
+        (
+            "--audio waveform",
+            "Show the audio track as a waveform below the visual timeline.",
+        ),
         (
             "--thumbnails thumbnails.vtt",
             "Generate thumbnail grids and a corresponding VTT file referencing their locations.",
         ),
+        (
+            "--thumbnails thumbnails.vtt --html preview.html",
+            "Generate timeline and thumbnails, and a web page to preview them with the video.",
+        ),
+        (
+            "--bif video.bif --bif-interval 5",
+            "Generate a BIF file for trick play, containing one thumbnail every 5 seconds.",
+        ),
+        (
+            "part2.mp4 part3.mp4",
+            "Generate a single timeline for several files, which are played one after another.",
+        ),
     ];
     let examples = examples
         .iter()
@@ -135,10 +546,168 @@ fn parse_config() -> Config {
         .arg(
             Arg::with_name("input file")
                 .value_name("INPUT_FILE")
-                .help("Name of the input video file.")
+                .help(
+                    "Name of the input video file, or a URI which GStreamer can open, like \
+                     `https://example.com/video.mp4` or `rtsp://example.com/stream`. Use `-` to \
+                     read the video from stdin. Several files, or a list of files (an M3U \
+                     playlist, an FFmpeg concat list with the extension `.ffconcat` or `.txt`, \
+                     or an EDL, whose events select ranges of the files by their source \
+                     timecodes), are treated as one continuous video. A directory, or a pattern \
+                     like `frames/*.png`, is read as a sequence of numbered images. Files which only \
+                     contain audio are shown as a spectrogram.",
+                )
                 .index(1)
+                .multiple(true)
                 .required(true),
         )
+        .arg(
+            Arg::with_name("stream")
+                .help(
+                    "Video stream to use, if the input file contains several. Either the stream's \
+                     index, starting at 0, its identifier, or its language code. Use \
+                     `--list-streams` to see which streams are available [default: 0].",
+                )
+                .long("stream")
+                .display_order(1)
+                .takes_value(true)
+                .value_name("STREAM"),
+        )
+        .arg(
+            Arg::with_name("list streams")
+                .help("List the video streams of the input file, and exit.")
+                .long("list-streams")
+                .display_order(2),
+        )
+        .arg(
+            Arg::with_name("compare")
+                .help(
+                    "Instead of generating any outputs, compare INPUT_FILE to the specified \
+                     video, using fingerprints of their colors over time. Prints how similar \
+                     they are, and which positions correspond to each other, even if one of \
+                     them is an excerpt, or plays at a different speed. Both files may also be \
+                     fingerprints written by `--fingerprint`.",
+                )
+                .long("compare")
+                .display_order(79)
+                .value_name("FILE")
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "timeline",
+                    "thumbnails",
+                    "bif",
+                    "manifest",
+                    "html",
+                    "audio",
+                    "cuts",
+                    "cut markers",
+                    "qc report",
+                    "flash report",
+                    "fingerprint",
+                ]),
+        )
+        .arg(
+            Arg::with_name("framerate")
+                .help(
+                    "Framerate of image sequences, in frames per second, either as a number or \
+                     as a fraction like `30000/1001` [default: 24].",
+                )
+                .long("framerate")
+                .display_order(11)
+                .takes_value(true)
+                .value_name("FPS"),
+        )
+        .arg(
+            Arg::with_name("start")
+                .help(
+                    "Only process the input file from this position on, given in seconds or as \
+                     `[HH:]MM:SS[.mmm]` [default: 0].",
+                )
+                .long("start")
+                .display_order(3)
+                .takes_value(true)
+                .value_name("TIME"),
+        )
+        .arg(
+            Arg::with_name("end")
+                .help(
+                    "Only process the input file up to this position, given in seconds or as \
+                     `[HH:]MM:SS[.mmm]` [default: end of the input file].",
+                )
+                .long("end")
+                .display_order(4)
+                .takes_value(true)
+                .value_name("TIME"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .help(
+                    "Only process the input file for this long after the start position, given in \
+                     seconds or as `[HH:]MM:SS[.mmm]`.",
+                )
+                .long("duration")
+                .display_order(5)
+                .takes_value(true)
+                .value_name("TIME")
+                .conflicts_with("end"),
+        )
+        .arg(
+            Arg::with_name("relative timestamps")
+                .help(
+                    "Make the timestamps in the outputs relative to the start position, instead \
+                     of to the beginning of the input file.",
+                )
+                .long("relative-timestamps")
+                .display_order(6),
+        )
+        .arg(
+            Arg::with_name("ignore duration")
+                .help(
+                    "Ignore the duration the input file reports, and decode it until the end to \
+                     find out how long it is. This is useful for files which are still growing, \
+                     or which report a wrong duration. Inputs which don't report a duration at \
+                     all are always handled like this.",
+                )
+                .long("ignore-duration")
+                .display_order(7),
+        )
+        .arg(
+            Arg::with_name("crop")
+                .help(
+                    "Remove borders from the video before generating timeline and thumbnails. \
+                     Either `auto`, which detects black borders like letterboxing and \
+                     pillarboxing in some frames of the processed range, `none`, or the number \
+                     of pixels to remove from each side, in the form `TOP,RIGHT,BOTTOM,LEFT` \
+                     [default: none]. Detection seeks in the input, so `auto` doesn't work for \
+                     stdin, image sequences, and inputs which can't seek.",
+                )
+                .long("crop")
+                .display_order(8)
+                .takes_value(true)
+                .value_name("CROP"),
+        )
+        .arg(
+            Arg::with_name("deinterlace")
+                .help(
+                    "Whether to deinterlace the video. Either `auto`, which only deinterlaces \
+                     frames which are marked as interlaced, `on`, or `off` [default: auto].",
+                )
+                .long("deinterlace")
+                .display_order(9)
+                .takes_value(true)
+                .value_name("MODE"),
+        )
+        .arg(
+            Arg::with_name("deinterlace method")
+                .help(
+                    "Method used for deinterlacing. One of `linear`, `linearblend`, \
+                     `scalerbob`, `vfir`, `greedyl`, `greedyh` or `tomsmocomp` [default: \
+                     chosen by GStreamer].",
+                )
+                .long("deinterlace-method")
+                .display_order(10)
+                .takes_value(true)
+                .value_name("METHOD"),
+        )
         .arg(
             Arg::with_name("width")
                 .help(
@@ -147,7 +716,7 @@ fn parse_config() -> Config {
                 )
                 .short("w")
                 .long("width")
-                .display_order(10)
+                .display_order(15)
                 .takes_value(true)
                 .value_name("NUM"),
         )
@@ -160,13 +729,71 @@ fn parse_config() -> Config {
                 .takes_value(true)
                 .value_name("NUM"),
         )
+        .arg(
+            Arg::with_name("bands")
+                .help(
+                    "Divide each frame into the specified number of horizontal bands of equal \
+                     height, and show their average colors as blocks in the visual timeline, \
+                     instead of resampling the frame row by row.",
+                )
+                .long("bands")
+                .display_order(24)
+                .takes_value(true)
+                .value_name("NUM"),
+        )
+        .arg(
+            Arg::with_name("flat")
+                .help(
+                    "Show only the average color of each frame in the visual timeline, which is \
+                     the same as `--bands 1`.",
+                )
+                .long("flat")
+                .display_order(26)
+                .conflicts_with("bands"),
+        )
+        .arg(
+            Arg::with_name("audio")
+                .help(
+                    "Analyze the audio track, and show it in a strip below the visual timeline. \
+                     Either `waveform`, which shows the peak and RMS amplitude of the samples, or \
+                     `loudness`, which shows the loudness in LUFS as colored bars.",
+                )
+                .long("audio")
+                .display_order(27)
+                .takes_value(true)
+                .value_name("STYLE"),
+        )
+        .arg(
+            Arg::with_name("audio height")
+                .help(
+                    "Height of the audio strip in pixels [default: a third of the timeline \
+                     height, but at least 16].",
+                )
+                .long("audio-height")
+                .display_order(28)
+                .takes_value(true)
+                .value_name("NUM")
+                .requires("audio"),
+        )
+        .arg(
+            Arg::with_name("audio strip")
+                .help(
+                    "Write the audio strip to the specified file in JPEG format, instead of \
+                     appending it to the visual timeline.",
+                )
+                .long("audio-strip")
+                .display_order(29)
+                .takes_value(true)
+                .value_name("JPEG_FILE")
+                .requires("audio"),
+        )
         .arg(
             Arg::with_name("timeline")
                 .help(
                     "Create a visual timeline from the input file, which visualizes its color \
                      development. The result will be written to the specified file in JPEG format \
-                     [default, if neither `--timeline` nor `--thumbnails` is used: \
-                     INPUT_FILE.timeline.jpg].",
+                     [default, if none of `--timeline`, `--thumbnails` and `--bif` is used, or if \
+                     `--html` is used: INPUT_FILE.timeline.jpg].",
                 )
                 .long("timeline")
                 .value_name("JPEG_FILE")
@@ -198,8 +825,233 @@ fn parse_config() -> Config {
                 .takes_value(true)
                 .value_name("NUM")
                 .display_order(50)
+                .requires("thumbnail outputs"),
+        )
+        .arg(
+            Arg::with_name("pick thumbnails")
+                .help(
+                    "Instead of using the first frame of each thumbnail's interval, pick the \
+                     most representative one: a frame which is not next to a cut, not black or \
+                     blank, and as sharp as possible. This decodes more frames, so it is slower. \
+                     It needs to know the duration of the input, so it can't be combined with \
+                     `--ignore-duration` or stdin, and has no effect on live sources.",
+                )
+                .long("pick-thumbnails")
+                .display_order(55)
+                .requires("thumbnail outputs"),
+        )
+        .arg(
+            Arg::with_name("dedup thumbnails")
+                .help(
+                    "Store thumbnails which look the same, like those of a static scene or a \
+                     slate, only once in the thumbnail grids. Their cues in the VTT file all \
+                     point to the same position, so there are fewer and smaller grids.",
+                )
+                .long("dedup-thumbnails")
+                .display_order(56)
                 .requires("thumbnails"),
         )
+        .arg(
+            Arg::with_name("bif")
+                .help(
+                    "Extract thumbnails from the input file, and write them to the specified \
+                     location as a BIF file, which is used for trick play on Roku and Plex \
+                     clients.",
+                )
+                .long("bif")
+                .display_order(60)
+                .value_name("BIF_FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bif interval")
+                .help("Number of seconds between two thumbnails in the BIF file [default: 10].")
+                .long("bif-interval")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .display_order(70)
+                .requires("bif"),
+        )
+        .arg(
+            Arg::with_name("manifest")
+                .help(
+                    "Write a JSON file to the specified location, which describes the input file \
+                     and all generated outputs, including the time range and position of every \
+                     thumbnail.",
+                )
+                .long("manifest")
+                .display_order(80)
+                .value_name("JSON_FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fingerprint")
+                .help(
+                    "Write a fingerprint of the input to the specified location as a CSV file, \
+                     which contains the average color of each column of the visual timeline. \
+                     It can be compared to other videos using `--compare`.",
+                )
+                .long("fingerprint")
+                .display_order(78)
+                .value_name("CSV_FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cuts")
+                .help(
+                    "Detect cuts between shots, and write them to the specified location. The \
+                     format depends on the extension: `.csv` lists the cuts, `.edl` describes \
+                     each shot as an event of a CMX 3600 edit decision list, and `.vtt` as a \
+                     WebVTT chapter.",
+                )
+                .long("cuts")
+                .display_order(82)
+                .value_name("FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cut threshold")
+                .help(
+                    "How different two consecutive frames have to be to count as a cut, from \
+                     0 to 1. Lower values find more cuts, but also mistake fast motion and \
+                     flashes for cuts [default: 0.4].",
+                )
+                .long("cut-threshold")
+                .display_order(84)
+                .value_name("NUM")
+                .takes_value(true)
+                .requires("cut detection"),
+        )
+        .arg(
+            Arg::with_name("cut markers")
+                .help("Mark the detected cuts on the visual timeline with red lines.")
+                .long("cut-markers")
+                .display_order(86),
+        )
+        .arg(
+            Arg::with_name("qc report")
+                .help(
+                    "Check the input for spans of black frames, frozen frames and, if `--audio` \
+                     is used, silence, and write them to the specified location as a JSON \
+                     file.",
+                )
+                .long("qc-report")
+                .display_order(87)
+                .value_name("JSON_FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("qc overlay")
+                .help("Highlight the spans from the QC report on the visual timeline.")
+                .long("qc-overlay")
+                .display_order(88)
+                .requires("qc report"),
+        )
+        .arg(
+            Arg::with_name("flash report")
+                .help(
+                    "Check the input for flashing which might trigger seizures in people with \
+                     photosensitive epilepsy, meaning more than three flashes within one \
+                     second, either of the luminance or of saturated red. The spans with such \
+                     flashing are written to the specified location as a JSON file. This \
+                     decodes more frames, so it is slower.",
+                )
+                .long("flash-report")
+                .display_order(95)
+                .value_name("JSON_FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("flash markers")
+                .help(
+                    "Mark harmful flashing along the top of the visual timeline, in red for red \
+                     flashes, and in yellow for others.",
+                )
+                .long("flash-markers")
+                .display_order(96)
+                .requires("flash report"),
+        )
+        .arg(
+            Arg::with_name("palette")
+                .help(
+                    "Extract color palettes, made up of the most common colors and their \
+                     shares, for the whole input and for each of its scenes, and write them to \
+                     the specified location as a JSON file.",
+                )
+                .long("palette")
+                .display_order(91)
+                .value_name("JSON_FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("palette swatches")
+                .help(
+                    "Draw the color palettes as an image of the same size as the visual \
+                     timeline, and write it to the specified location in JPEG format. The \
+                     palette of the whole input is shown along the top, and below it, each \
+                     column shows the palette of its scene.",
+                )
+                .long("palette-swatches")
+                .display_order(92)
+                .value_name("JPEG_FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("palette colors")
+                .help("Number of colors in each palette [default: 5].")
+                .long("palette-colors")
+                .display_order(93)
+                .value_name("NUM")
+                .takes_value(true)
+                .requires("palette outputs"),
+        )
+        .arg(
+            Arg::with_name("palette interval")
+                .help(
+                    "Extract palettes for intervals of the specified number of seconds, \
+                     instead of for scenes.",
+                )
+                .long("palette-interval")
+                .display_order(94)
+                .value_name("SECONDS")
+                .takes_value(true)
+                .requires("palette outputs"),
+        )
+        .arg(
+            Arg::with_name("html")
+                .help(
+                    "Write a static web page to the specified location, which plays the input \
+                     file and uses the visual timeline as a seek bar. If `--thumbnails` is used, \
+                     hovering over the timeline shows the thumbnails as previews. It requires \
+                     `--timeline`.",
+                )
+                .long("html")
+                .display_order(90)
+                .value_name("HTML_FILE")
+                .takes_value(true)
+                .requires("timeline"),
+        )
+        .group(
+            ArgGroup::with_name("thumbnail outputs")
+                .args(&["thumbnails", "bif"])
+                .multiple(true),
+        )
+        .group(
+            ArgGroup::with_name("palette outputs")
+                .args(&["palette", "palette swatches"])
+                .multiple(true),
+        )
+        .group(
+            ArgGroup::with_name("cut detection")
+                .args(&[
+                    "cuts",
+                    "cut markers",
+                    "pick thumbnails",
+                    "palette",
+                    "palette swatches",
+                ])
+                .multiple(true),
+        )
         // Hack to remove the autogenerated -V option, see
         // https://github.com/kbknapp/clap-rs/issues/1316.
         .arg(Arg::with_name("remove short version").short("V").hidden(true))
@@ -238,108 +1090,690 @@ fn parse_config() -> Config {
         if width.is_none() {
             width = Some(1000);
         }
-        height = Some(width.expect("Could not read default width") / 10);
-    } else if width.is_none() {
-        width = Some(height.expect("Could not read default height") * 10);
-    }
+        height = Some(width.expect("Could not read default width") / 10);
+    } else if width.is_none() {
+        width = Some(height.expect("Could not read default height") * 10);
+    }
+
+    if width.expect("Could not read width") < 16 {
+        error("Timeline width must be at least 16");
+    }
+
+    if height.expect("Could not read height") < 16 {
+        error("Timeline height must be at least 16");
+    }
+
+    if width.expect("Could not read width, again") > 10000 {
+        error("Timeline width must be at most 10000");
+    }
+
+    if height.expect("Could not read height, again") > 10000 {
+        error("Timeline height must be at most 10000");
+    }
+
+    // Set number of bands
+    let bands = if matches.is_present("flat") {
+        Some(1)
+    } else if matches.is_present("bands") {
+        let bands_string = matches
+            .value_of("bands")
+            .expect("Could not get bands from command line parser");
+
+        let bands: usize = if let Ok(bands) = bands_string.parse() {
+            bands
+        } else {
+            error("Number of bands must be an integer");
+        };
+
+        if bands < 1 {
+            error("Number of bands must be at least 1");
+        }
+
+        if bands > height.expect("Could not read height for bands") {
+            error("Number of bands must be at most the timeline height");
+        }
+
+        Some(bands)
+    } else {
+        None
+    };
+
+    //Set thumbnail height
+    let thumbnail_height_string = matches.value_of("thumbnail height").unwrap_or("90");
+    let thumbnail_height: usize = if let Ok(thumbnail_height) = thumbnail_height_string.parse() {
+        thumbnail_height
+    } else {
+        error("Thumbnail height must be an integer");
+    };
+
+    if thumbnail_height < 16 {
+        error("Thumbnail height must be at least 16");
+    }
+
+    if thumbnail_height > 10000 {
+        error("Thumbnail height must be at most 10000");
+    }
+
+    let mut input_filenames: Vec<String> = matches
+        .values_of("input file")
+        .expect("Could not get input file from command line parser")
+        .map(String::from)
+        .collect();
+    let input_filename = input_filenames[0].clone();
+    let mut input_edits = vec![None; input_filenames.len()];
+
+    if input_filenames.len() == 1 {
+        match playlist::read(&input_filename) {
+            Ok(Some(entries)) => {
+                let (filenames, edits) = entries.into_iter().unzip();
+                input_filenames = filenames;
+                input_edits = edits;
+            }
+            Ok(None) => {}
+            Err(message) => error(&message),
+        }
+    }
+
+    if matches.is_present("list streams") {
+        list_streams(&input_filenames[0]);
+    }
+
+    // Set the range to process
+    let start = match matches.value_of("start") {
+        Some(start_string) => match parse_time(start_string) {
+            Some(start) => start,
+            None => error("Start must be a time in seconds, or in the form `[HH:]MM:SS[.mmm]`"),
+        },
+        None => 0.0,
+    };
+
+    let end = if let Some(end_string) = matches.value_of("end") {
+        match parse_time(end_string) {
+            Some(end) => Some(end),
+            None => error("End must be a time in seconds, or in the form `[HH:]MM:SS[.mmm]`"),
+        }
+    } else if let Some(duration_string) = matches.value_of("duration") {
+        match parse_time(duration_string) {
+            Some(duration) => Some(start + duration),
+            None => error("Duration must be a time in seconds, or in the form `[HH:]MM:SS[.mmm]`"),
+        }
+    } else {
+        None
+    };
+
+    if let Some(end) = end {
+        if end <= start {
+            error("End must be after start");
+        }
+    }
+
+    // Set borders to crop
+    let crop = match matches.value_of("crop").unwrap_or("none") {
+        "auto" => None,
+        "none" => Some(source::Crop::default()),
+        crop_string => {
+            let sides: Vec<usize> = crop_string
+                .split(',')
+                .map(|side| side.parse())
+                .collect::<Result<Vec<usize>, _>>()
+                .unwrap_or_else(|_| vec![]);
+
+            if sides.len() != 4 {
+                error("Crop must be `auto`, `none`, or four integers like `TOP,RIGHT,BOTTOM,LEFT`");
+            }
+
+            Some(source::Crop {
+                top: sides[0],
+                right: sides[1],
+                bottom: sides[2],
+                left: sides[3],
+            })
+        }
+    };
+
+    let enabled = match matches.value_of("deinterlace").unwrap_or("auto") {
+        "auto" => None,
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => error("Deinterlace must be `auto`, `on` or `off`."),
+    };
+    let method = matches.value_of("deinterlace method").map(String::from);
+    if let (Some(false), Some(_)) = (enabled, &method) {
+        error("A deinterlace method can't be chosen if deinterlacing is `off`.");
+    }
+    if let Some(ref method) = method {
+        if !source::DEINTERLACE_METHODS.contains(&method.as_str()) {
+            error(&format!(
+                "Deinterlace method must be one of {}.",
+                source::DEINTERLACE_METHODS.join(", ")
+            ));
+        }
+    }
+    let deinterlace = source::Deinterlace { enabled, method };
+
+    // Other inputs have their own framerate
+    if matches.is_present("framerate") && !input_filenames.iter().any(|input| is_sequence(input)) {
+        error("A framerate can only be set for image sequences.");
+    }
+    let framerate = match matches.value_of("framerate") {
+        Some(framerate_string) => match parse_framerate(framerate_string) {
+            Some(framerate) => framerate,
+            None => error("Framerate must be a positive integer, or a fraction like `30000/1001`"),
+        },
+        None => (24, 1),
+    };
+
+    // Set how to show the audio track
+    let audio = match matches.value_of("audio") {
+        Some("waveform") => Some(audio::Style::Waveform),
+        Some("loudness") => Some(audio::Style::Loudness),
+        Some(_) => error("Audio must be `waveform` or `loudness`."),
+        None => None,
+    };
+
+    let audio_height = match matches.value_of("audio height") {
+        Some(audio_height_string) => {
+            let audio_height: usize = if let Ok(audio_height) = audio_height_string.parse() {
+                audio_height
+            } else {
+                error("Audio height must be an integer");
+            };
+
+            if audio_height < 16 {
+                error("Audio height must be at least 16");
+            }
+
+            if audio_height > 10000 {
+                error("Audio height must be at most 10000");
+            }
+
+            audio_height
+        }
+        None => cmp::max(height.expect("Could not read height for audio") / 3, 16),
+    };
+
+    let audio_filename = matches.value_of("audio strip").map(String::from);
+    if let Some(ref audio_filename) = audio_filename {
+        if Path::new(audio_filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            != Some("jpg")
+        {
+            error("You must specify a .jpg file as an output for `--audio-strip`.");
+        }
+    }
+
+    // Set timeline filename
+    let timeline_filename = if matches.is_present("timeline") {
+        let arg = String::from(
+            matches
+                .value_of("timeline")
+                .expect("Could not get timeline option from command line parser"),
+        );
+        let path = PathBuf::from(&arg);
+        if path.extension().is_none()
+            || path
+                .extension()
+                .expect("Could not get extension from timeline argument")
+                != "jpg"
+        {
+            error("You must specify a .jpg file as an output for `--timeline`.");
+        }
+        Some(String::from(matches.value_of("timeline").expect(
+            "Could not get timeline option from command line parser, again",
+        )))
+    } else if !matches.is_present("thumbnails") && !matches.is_present("bif") {
+        Some(format!("{}.timeline.jpg", output_base(&input_filename)))
+    } else {
+        None
+    };
+
+    if audio.is_some() && audio_filename.is_none() && timeline_filename.is_none() {
+        error("The audio strip is appended to the visual timeline, so `--audio` requires `--timeline`, or `--audio-strip`.");
+    }
+
+    // Set fingerprint-related options
+    let fingerprint_filename = if matches.is_present("fingerprint") {
+        let arg = String::from(
+            matches
+                .value_of("fingerprint")
+                .expect("Could not get fingerprint option from command line parser"),
+        );
+        if Path::new(&arg)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            != Some("csv")
+        {
+            error("You must specify a .csv file as an output for `--fingerprint`.");
+        }
+        Some(arg)
+    } else {
+        None
+    };
+
+    let compare_filename = matches.value_of("compare").map(String::from);
+    if compare_filename.is_some() && input_filenames.len() > 1 {
+        error("Only a single input file can be compared.");
+    }
+
+    // Set cut-related options
+    let cuts_filename = if matches.is_present("cuts") {
+        let arg = String::from(
+            matches
+                .value_of("cuts")
+                .expect("Could not get cuts option from command line parser"),
+        );
+        match Path::new(&arg)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("csv") | Some("edl") | Some("vtt") => {}
+            _ => error("You must specify a .csv, .edl or .vtt file as an output for `--cuts`."),
+        }
+        Some(arg)
+    } else {
+        None
+    };
+
+    let cut_threshold_string = matches.value_of("cut threshold").unwrap_or("0.4");
+    let cut_threshold: f32 = if let Ok(cut_threshold) = cut_threshold_string.parse() {
+        cut_threshold
+    } else {
+        error("Cut threshold must be a number");
+    };
+
+    if !(cut_threshold > 0.0 && cut_threshold <= 1.0) {
+        error("Cut threshold must be greater than 0, and at most 1");
+    }
+
+    let cut_markers = matches.is_present("cut markers");
+    if cut_markers && timeline_filename.is_none() {
+        error("The cut markers are drawn onto the visual timeline, so `--cut-markers` requires `--timeline`.");
+    }
+
+    // Set QC-related options
+    let qc_filename = if matches.is_present("qc report") {
+        let arg = String::from(
+            matches
+                .value_of("qc report")
+                .expect("Could not get QC report option from command line parser"),
+        );
+        if Path::new(&arg)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            != Some("json")
+        {
+            error("You must specify a .json file as an output for `--qc-report`.");
+        }
+        Some(arg)
+    } else {
+        None
+    };
+
+    let qc_overlay = matches.is_present("qc overlay");
+    if qc_overlay && timeline_filename.is_none() {
+        error("The QC overlay is drawn onto the visual timeline, so `--qc-overlay` requires `--timeline`.");
+    }
+
+    // Set flash-related options
+    let flash_filename = if matches.is_present("flash report") {
+        let arg = String::from(
+            matches
+                .value_of("flash report")
+                .expect("Could not get flash report option from command line parser"),
+        );
+        if Path::new(&arg)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            != Some("json")
+        {
+            error("You must specify a .json file as an output for `--flash-report`.");
+        }
+        Some(arg)
+    } else {
+        None
+    };
+
+    let flash_markers = matches.is_present("flash markers");
+    if flash_markers && timeline_filename.is_none() {
+        error("The flash markers are drawn onto the visual timeline, so `--flash-markers` requires `--timeline`.");
+    }
+
+    // Set palette-related options
+    let palette_filename = if matches.is_present("palette") {
+        let arg = String::from(
+            matches
+                .value_of("palette")
+                .expect("Could not get palette option from command line parser"),
+        );
+        if Path::new(&arg)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            != Some("json")
+        {
+            error("You must specify a .json file as an output for `--palette`.");
+        }
+        Some(arg)
+    } else {
+        None
+    };
+
+    let swatches_filename = if matches.is_present("palette swatches") {
+        let arg = String::from(
+            matches
+                .value_of("palette swatches")
+                .expect("Could not get palette swatches option from command line parser"),
+        );
+        if Path::new(&arg)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            != Some("jpg")
+        {
+            error("You must specify a .jpg file as an output for `--palette-swatches`.");
+        }
+        Some(arg)
+    } else {
+        None
+    };
 
-    if width.expect("Could not read width") < 16 {
-        error("Timeline width must be at least 16");
-    }
+    let palette_colors_string = matches.value_of("palette colors").unwrap_or("5");
+    let palette_colors: usize = if let Ok(palette_colors) = palette_colors_string.parse() {
+        palette_colors
+    } else {
+        error("Palette colors must be an integer");
+    };
 
-    if height.expect("Could not read height") < 16 {
-        error("Timeline height must be at least 16");
+    if palette_colors < 1 {
+        error("Palette colors must be at least 1");
     }
 
-    if width.expect("Could not read width, again") > 10000 {
-        error("Timeline width must be at most 10000");
-    }
+    let palette_interval = match matches.value_of("palette interval") {
+        Some(palette_interval_string) => match palette_interval_string.parse::<f32>() {
+            Ok(palette_interval) if palette_interval > 0.0 => Some(palette_interval),
+            Ok(_) => error("Palette interval must be greater than 0"),
+            Err(_) => error("Palette interval must be a number"),
+        },
+        None => None,
+    };
 
-    if height.expect("Could not read height, again") > 10000 {
-        error("Timeline height must be at most 10000");
-    }
+    // Set thumbnail-related filenames
+    let vtt_filename = if matches.is_present("thumbnails") {
+        let arg = String::from(
+            matches
+                .value_of("thumbnails")
+                .expect("Could not get thumbnails option from command line parser"),
+        );
+        let path = PathBuf::from(&arg);
+        if path.extension().is_none()
+            || path
+                .extension()
+                .expect("Could not get extension from thumbnails argument")
+                != "vtt"
+        {
+            error("You must specify a .vtt file as an output for `--thumbnails`.");
+        }
+        Some(arg)
+    } else {
+        None
+    };
 
-    //Set thumbnail height
-    let thumbnail_height_string = matches.value_of("thumbnail height").unwrap_or("90");
-    let thumbnail_height: usize = if let Ok(thumbnail_height) = thumbnail_height_string.parse() {
-        thumbnail_height
+    // Set BIF filename and interval
+    let bif_filename = if matches.is_present("bif") {
+        let arg = String::from(
+            matches
+                .value_of("bif")
+                .expect("Could not get BIF option from command line parser"),
+        );
+        let path = PathBuf::from(&arg);
+        if path.extension().is_none()
+            || path
+                .extension()
+                .expect("Could not get extension from BIF argument")
+                != "bif"
+        {
+            error("You must specify a .bif file as an output for `--bif`.");
+        }
+        Some(arg)
     } else {
-        error("Thumbnail height must be an integer");
+        None
     };
 
-    if thumbnail_height < 16 {
-        error("Thumbnail height must be at least 16");
-    }
+    let bif_interval_string = matches.value_of("bif interval").unwrap_or("10");
+    let bif_interval: u32 = if let Ok(bif_interval) = bif_interval_string.parse() {
+        bif_interval
+    } else {
+        error("BIF interval must be an integer");
+    };
 
-    if thumbnail_height > 10000 {
-        error("Thumbnail height must be at most 10000");
+    if bif_interval < 1 {
+        error("BIF interval must be at least 1");
     }
 
-    let input_filename = matches
-        .value_of("input file")
-        .expect("Could not get input file from command line parser");
-
-    // Set timeline filename
-    let timeline_filename = if matches.is_present("timeline") {
+    // Set manifest filename
+    let manifest_filename = if matches.is_present("manifest") {
         let arg = String::from(
             matches
-                .value_of("timeline")
-                .expect("Could not get timeline option from command line parser"),
+                .value_of("manifest")
+                .expect("Could not get manifest option from command line parser"),
         );
         let path = PathBuf::from(&arg);
         if path.extension().is_none()
             || path
                 .extension()
-                .expect("Could not get extension from timeline argument")
-                != "jpg"
+                .expect("Could not get extension from manifest argument")
+                != "json"
         {
-            error("You must specify a .jpg file as an output for `--timeline`.");
+            error("You must specify a .json file as an output for `--manifest`.");
         }
-        Some(String::from(matches.value_of("timeline").expect(
-            "Could not get timeline option from command line parser, again",
-        )))
-    } else if !matches.is_present("thumbnails") {
-        Some(format!("{}.timeline.jpg", &input_filename))
+        Some(arg)
     } else {
         None
     };
 
-    // Set thumbnail-related filenames
-    let vtt_filename = if matches.is_present("thumbnails") {
+    // Set HTML filename
+    let html_filename = if matches.is_present("html") {
         let arg = String::from(
             matches
-                .value_of("thumbnails")
-                .expect("Could not get thumbnails option from command line parser"),
+                .value_of("html")
+                .expect("Could not get HTML option from command line parser"),
         );
         let path = PathBuf::from(&arg);
         if path.extension().is_none()
             || path
                 .extension()
-                .expect("Could not get extension from thumbnails argument")
-                != "vtt"
+                .expect("Could not get extension from HTML argument")
+                != "html"
         {
-            error("You must specify a .vtt file as an output for `--thumbnails`.");
+            error("You must specify a .html file as an output for `--html`.");
+        }
+        if input_filename == "-" {
+            error("The preview page can't play a video which was read from stdin.");
+        }
+        if input_filenames.len() > 1 {
+            error("The preview page can only play a single input file.");
+        }
+        if is_sequence(&input_filenames[0]) {
+            error("The preview page can't play an image sequence.");
         }
         Some(arg)
     } else {
         None
     };
 
-    check_for_collision(&input_filename, &timeline_filename);
-    check_for_collision(&input_filename, &vtt_filename);
+    if matches.is_present("pick thumbnails")
+        && (matches.is_present("ignore duration") || input_filename == "-")
+    {
+        error("Thumbnails can only be picked for inputs with a known duration.");
+    }
+
+    for existing in input_filenames.iter().chain(Some(&input_filename)) {
+        check_for_collision(&existing, &timeline_filename);
+        check_for_collision(&existing, &audio_filename);
+        check_for_collision(&existing, &cuts_filename);
+        check_for_collision(&existing, &fingerprint_filename);
+        check_for_collision(&existing, &qc_filename);
+        check_for_collision(&existing, &flash_filename);
+        check_for_collision(&existing, &palette_filename);
+        check_for_collision(&existing, &swatches_filename);
+        check_for_collision(&existing, &vtt_filename);
+        check_for_collision(&existing, &bif_filename);
+        check_for_collision(&existing, &manifest_filename);
+        check_for_collision(&existing, &html_filename);
+    }
 
     Config {
         width: width.expect("Could not read width, part 3"),
         height: height.expect("Could not read height, part 3"),
+        bands,
 
         thumbnail_width: 0,
         thumbnail_height,
         thumbnail_columns: 0,
-
-        input_filename: String::from(input_filename),
+        pick_thumbnails: matches.is_present("pick thumbnails"),
+        dedup_thumbnails: matches.is_present("dedup thumbnails"),
+
+        input_filename,
+        input_filenames,
+        input_edits,
+        start,
+        end,
+        relative_timestamps: matches.is_present("relative timestamps"),
+        ignore_duration: matches.is_present("ignore duration"),
+        deinterlace,
+        framerate,
+        crop,
+        stream: matches.value_of("stream").map(String::from),
+        audio,
+        audio_height,
+        audio_filename,
+        cuts_filename,
+        cut_threshold,
+        cut_markers,
+        qc_filename,
+        qc_overlay,
+        flash_filename,
+        flash_markers,
+        palette_filename,
+        swatches_filename,
+        palette_colors,
+        palette_interval,
+        fingerprint_filename,
+        compare_filename,
         timeline_filename,
         vtt_filename,
+        bif_filename,
+        bif_interval,
+        manifest_filename,
+        html_filename,
+    }
+}
+
+// Returns the prefix of default output filenames for `input`. For URIs, this is the last part of
+// their path, so that outputs are written to the current directory.
+fn output_base(input: &str) -> String {
+    if input == "-" {
+        return String::from("stdin");
+    }
+
+    // Place outputs next to the directory of an image sequence
+    if is_sequence(&input) {
+        let directory = if sequence::is_glob(&input) {
+            Path::new(input).parent().unwrap_or_else(|| Path::new(""))
+        } else {
+            Path::new(input)
+        };
+        return match directory.file_name() {
+            Some(name) => directory
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(name)
+                .to_string_lossy()
+                .into_owned(),
+            None => String::from("sequence"),
+        };
+    }
+
+    if !source::is_uri(&input) {
+        return String::from(input);
+    }
+
+    let path = input.split(|c| c == '?' || c == '#').next().unwrap_or("");
+    match path.rsplit('/').next() {
+        Some(name) if !name.is_empty() && !path.ends_with("://") => String::from(name),
+        _ => String::from("stream"),
+    }
+}
+
+// Print the video streams of `input_filename`, and exit
+fn list_streams(input_filename: &str) -> ! {
+    let streams = match source::list_streams(&input_filename) {
+        Ok(streams) => streams,
+        Err(message) => error(&message),
+    };
+
+    println!("Video streams in '{}':", input_filename);
+    for stream in streams {
+        println!(
+            "  {}: {}x{}, rotation: {}, language: {}, identifier: {}",
+            stream.index,
+            stream.width,
+            stream.height,
+            stream.rotation,
+            stream.language.unwrap_or_else(|| String::from("unknown")),
+            stream.id.unwrap_or_else(|| String::from("unknown"))
+        );
+    }
+
+    process::exit(0);
+}
+
+// Options for opening the input files as a VideoSource
+fn source_options(config: &Config) -> source::Options {
+    source::Options {
+        crop: config.crop,
+        stream: config.stream.clone(),
+        start: config.start,
+        end: config.end,
+        ignore_duration: config.ignore_duration,
+        deinterlace: config.deinterlace.clone(),
+        framerate: config.framerate,
+        audio: config.audio.is_some(),
+        min_framerate: config.min_framerate(),
+    }
+}
+
+// Compare the input file to `compare_filename` using their fingerprints, print the result, and
+// exit
+fn compare(config: &Config, compare_filename: &str) -> ! {
+    let options = source_options(&config);
+    let load = |filename: &str| {
+        println!("timelens: fingerprinting '{}'", filename);
+        match fingerprint::Fingerprint::load(filename, config, &options) {
+            Ok(fingerprint) => fingerprint,
+            Err(message) => error(&message),
+        }
+    };
+    let a = load(&config.input_filenames[0]);
+    let b = load(compare_filename);
+
+    match fingerprint::compare(&a, &b) {
+        Some(alignment) => {
+            println!("-> similarity: {:.3}", alignment.similarity);
+            println!(
+                "-> position t in '{}' corresponds to {:.4} * t {} {:.3} seconds in '{}'",
+                compare_filename,
+                alignment.scale,
+                if alignment.shift < 0.0 { "-" } else { "+" },
+                alignment.shift.abs(),
+                config.input_filenames[0]
+            );
+        }
+        None => {
+            println!("-> the inputs can't be compared, because their colors don't change");
+        }
     }
+
+    process::exit(0);
 }
 
 fn error(message: &str) -> ! {
@@ -351,6 +1785,10 @@ fn error(message: &str) -> ! {
 fn generate_timeline_and_thumbnails(
     config: &Config,
     source: &mut source::VideoSource,
+    detector: &mut Option<cuts::Detector>,
+    checker: &mut Option<qc::Checker>,
+    analyzer: &mut Option<flash::Analyzer>,
+    extractor: &mut Option<palette::Extractor>,
 ) -> (frame::Frame, Vec<frame::Frame>) {
     // Frame that will hold the visual timeline
     let mut timeline = frame::Frame::new(config.width, config.height);
@@ -358,8 +1796,7 @@ fn generate_timeline_and_thumbnails(
     let mut grids = vec![];
 
     // Frames that will hold the thumbnail grids
-    let grid_columns = MAX_GRID_WIDTH / config.thumbnail_width;
-    let grid_rows = MAX_GRID_HEIGHT / config.thumbnail_height;
+    let (grid_columns, grid_rows) = grid_dimensions(&config);
 
     let grid_count = config.width / (grid_columns * grid_rows) + 1;
     let grid_width = grid_columns * config.thumbnail_width;
@@ -372,33 +1809,124 @@ fn generate_timeline_and_thumbnails(
     // Keep track of which columns are already done
     let mut done = vec![0; config.width];
 
-    // Remember duration before moving `source`
+    // Picks the thumbnails, if they shouldn't simply be the first frame of their column. This
+    // needs to know the columns while decoding, so it only works if the duration is known.
+    if config.pick_thumbnails && !source.duration_known {
+        println!(
+            "-> {}: the duration of the input is unknown, so thumbnails can't be picked",
+            "warning".yellow().bold()
+        );
+    }
+    let mut selector = if config.pick_thumbnails && source.duration_known {
+        Some(selection::Selector::default())
+    } else {
+        None
+    };
+
+    // Remember start and duration before iterating over `source`
+    let start = source.start;
     let duration = source.duration;
+    let duration_known = source.duration_known;
+
+    // If the duration is unknown, we can't tell which column a frame belongs to until all of them
+    // were decoded. Until then, we buffer the columns of every `stride`-th frame, and double the
+    // stride whenever the buffer is full, so that the buffered frames stay evenly spread over the
+    // video. The thumbnails of every other buffered frame are kept in the grids, one per position,
+    // and are moved to their columns at the end.
+    let mut buffered = vec![];
+    let mut stride = 1;
+    let mut count = 0;
 
     let start_time = SystemTime::now();
 
     // Iterate over the frames from the source (which arrive in any order)
-    for frame in source {
-        // Calculate which column this frame belongs to
-        let i = cmp::min(
-            (config.width as f32
-                * (frame.pts.expect("Could not get PTS from source frame") / duration as f32))
-                as usize,
-            config.width - 1,
-        );
+    for frame in &mut *source {
+        let pts = frame.pts.expect("Could not get PTS from source frame");
 
-        let (file, x, y) = grid_position(i, &config);
+        // Cut detection, quality control and the palettes look at every frame, even if their
+        // columns are already done
+        if let Some(ref mut detector) = detector {
+            detector.add(&frame);
+        }
+        if let Some(ref mut checker) = checker {
+            checker.add(&frame);
+        }
+        if let Some(ref mut analyzer) = analyzer {
+            analyzer.add(&frame);
+        }
+        if let Some(ref mut extractor) = extractor {
+            match detector {
+                Some(ref detector) => extractor.add(&frame, &detector.cuts),
+                None => extractor.add(&frame, &[]),
+            }
+        }
+
+        if !duration_known {
+            count += 1;
+            if (count - 1) % stride != 0 {
+                continue;
+            }
+
+            // The frames have to be in order for the thumbnails to be moved in place
+            match buffered.last() {
+                Some(&(last, _)) if pts <= last => continue,
+                _ => (),
+            }
+
+            let column = render_column(&frame, config);
+            if buffered.len() % 2 == 0 && config.thumbnails_needed() {
+                let thumbnail = frame.scale(config.thumbnail_width, config.thumbnail_height);
+                place(
+                    buffered.len() / 2,
+                    None,
+                    Some(thumbnail),
+                    config,
+                    &mut timeline,
+                    &mut grids,
+                );
+            }
+            buffered.push((pts, column));
+            if buffered.len() >= 2 * config.width {
+                buffered = buffered.into_iter().step_by(2).collect();
+                for (j, from) in (0..buffered.len()).step_by(2).enumerate() {
+                    move_thumbnail(from, j, config, &mut grids);
+                }
+                stride *= 2;
+            }
 
-        if config.timeline_filename.is_some() {
-            // Scale frame to 1 pixel width and copy into the timeline
-            let column = frame.scale(1, config.height);
-            timeline.copy(&column, i, 0);
+            print!(
+                "\rtimelens: {} decoded ",
+                timestamp(((pts - start).max(0.0) * 1000.0) as i32)
+            );
+            stdout().flush().expect("Could not flush stdout");
+            continue;
         }
 
-        if config.vtt_filename.is_some() {
-            let thumbnail = frame.scale(config.thumbnail_width, config.thumbnail_height);
-            // Copy frame to the thumbnail grid
-            grids[file].copy(&thumbnail, x, y);
+        // Calculate which column this frame belongs to. If the source outputs more frames than
+        // there are columns, the first one of each column is used.
+        let i = column_index(pts - start, duration, &config);
+        if let Some(ref mut selector) = selector {
+            // Every frame is a candidate for the thumbnail of its column
+            if done[i] == 0 {
+                let column = render_column(&frame, &config);
+                place(i, column, None, &config, &mut timeline, &mut grids);
+            }
+            selector.add(
+                i,
+                pts,
+                frame.scale(config.thumbnail_width, config.thumbnail_height),
+            );
+
+            let cuts = &detector
+                .as_ref()
+                .expect("Could not get cut detector for thumbnail selection")
+                .cuts;
+            for (j, thumbnail) in selector.finish(Some(pts), cuts) {
+                place(j, None, Some(thumbnail), &config, &mut timeline, &mut grids);
+            }
+        } else if done[i] == 0 {
+            let (column, thumbnail) = render(&frame, &config);
+            place(i, column, thumbnail, &config, &mut timeline, &mut grids);
         }
 
         done[i as usize] += 1;
@@ -427,6 +1955,67 @@ fn generate_timeline_and_thumbnails(
         stdout().flush().expect("Could not flush stdout");
     }
 
+    if let Some(ref message) = source.failure {
+        println!();
+        error(message);
+    }
+
+    if !duration_known {
+        if buffered.is_empty() {
+            println!();
+            error("The input does not contain any frames.");
+        }
+
+        // Now that the source is exhausted, its duration is known
+        let duration = source.duration;
+        let targets: Vec<usize> = buffered
+            .iter()
+            .step_by(2)
+            .map(|(pts, _)| column_index(pts - start, duration, config))
+            .collect();
+        for (pts, column) in buffered {
+            let i = column_index(pts - start, duration, &config);
+            place(i, column, None, config, &mut timeline, &mut grids);
+        }
+
+        // Move the thumbnails to their columns. As the frames are in order, the targets never
+        // decrease, so moving the thumbnails which go backwards in ascending order, and the others
+        // in descending order, never overwrites one which hasn't been moved yet. There is only one
+        // candidate per column, so there is nothing to pick from.
+        if config.thumbnails_needed() {
+            let mut filled = vec![false; config.width];
+            for (k, &i) in targets.iter().enumerate() {
+                if i <= k && !filled[i] {
+                    move_thumbnail(k, i, config, &mut grids);
+                    filled[i] = true;
+                }
+            }
+            for (k, &i) in targets.iter().enumerate().rev() {
+                if i > k {
+                    move_thumbnail(k, i, config, &mut grids);
+                    filled[i] = true;
+                }
+            }
+
+            let blank = frame::Frame::new(config.thumbnail_width, config.thumbnail_height);
+            for i in (0..config.width).filter(|&i| !filled[i]) {
+                let (file, x, y) = grid_position(i, config);
+                grids[file].copy(&blank, x, y);
+            }
+        }
+    }
+
+    // Pick the thumbnails of the remaining columns, now that all cuts are known
+    if let Some(ref mut selector) = selector {
+        let cuts = &detector
+            .as_ref()
+            .expect("Could not get cut detector for thumbnail selection, part 2")
+            .cuts;
+        for (i, thumbnail) in selector.finish(None, cuts) {
+            place(i, None, Some(thumbnail), &config, &mut timeline, &mut grids);
+        }
+    }
+
     let elapsed = start_time.elapsed().unwrap_or(Duration::new(0, 0));
     let total_seconds = elapsed.as_secs() as f32 + elapsed.subsec_millis() as f32 / 1000.0;
     let elapsed_minutes = total_seconds as usize / 60;
@@ -442,6 +2031,123 @@ fn generate_timeline_and_thumbnails(
     (timeline, grids)
 }
 
+// Calculate which column a frame belongs to, which is `offset` seconds into the processed range of
+// `duration` seconds
+fn column_index(offset: f32, duration: f32, config: &Config) -> usize {
+    if duration <= 0.0 {
+        return 0;
+    }
+
+    cmp::min(
+        (config.width as f32 * (offset.max(0.0) / duration)) as usize,
+        config.width - 1,
+    )
+}
+
+// Scale `frame` down to what the requested outputs need: a column of the timeline, and a
+// thumbnail
+fn render(frame: &frame::Frame, config: &Config) -> (Option<frame::Frame>, Option<frame::Frame>) {
+    let column = render_column(frame, config);
+
+    let thumbnail = if config.thumbnails_needed() {
+        Some(frame.scale(config.thumbnail_width, config.thumbnail_height))
+    } else {
+        None
+    };
+
+    (column, thumbnail)
+}
+
+// Scale `frame` down to a column of the timeline, if one is needed
+fn render_column(frame: &frame::Frame, config: &Config) -> Option<frame::Frame> {
+    if config.timeline_needed() {
+        // Scale frame to 1 pixel width
+        Some(match config.bands {
+            Some(bands) => frame.bands(bands, config.height),
+            None => frame.scale(1, config.height),
+        })
+    } else {
+        None
+    }
+}
+
+// Copy the rendered parts of a frame into the i-th column of the timeline, and into the i-th
+// thumbnail position of the grids
+fn place(
+    i: usize,
+    column: Option<frame::Frame>,
+    thumbnail: Option<frame::Frame>,
+    config: &Config,
+    timeline: &mut frame::Frame,
+    grids: &mut [frame::Frame],
+) {
+    if let Some(column) = column {
+        timeline.copy(&column, i, 0);
+    }
+
+    if let Some(thumbnail) = thumbnail {
+        let (file, x, y) = grid_position(i, &config);
+        grids[file].copy(&thumbnail, x, y);
+    }
+}
+
+// Copy the thumbnail at the `from`-th position of the grids to the `to`-th position
+fn move_thumbnail(from: usize, to: usize, config: &Config, grids: &mut [frame::Frame]) {
+    if from == to {
+        return;
+    }
+
+    let (file, x, y) = grid_position(from, config);
+    let thumbnail = grids[file].crop(x, y, config.thumbnail_width, config.thumbnail_height);
+    let (file, x, y) = grid_position(to, config);
+    grids[file].copy(&thumbnail, x, y);
+}
+
+// Whether `input` refers to an image sequence, instead of a single file
+fn is_sequence(input: &str) -> bool {
+    Path::new(input).is_dir() || (sequence::is_glob(input) && !Path::new(input).exists())
+}
+
+// Parse a framerate given as an integer, or as a fraction like "30000/1001"
+fn parse_framerate(framerate: &str) -> Option<(u32, u32)> {
+    let mut parts = framerate.splitn(2, '/');
+    let numerator = parts.next()?.parse().ok()?;
+    let denominator = match parts.next() {
+        Some(denominator) => denominator.parse().ok()?,
+        None => 1,
+    };
+
+    if numerator == 0 || denominator == 0 || numerator > i32::max_value() as u32 {
+        return None;
+    }
+
+    Some((numerator, denominator))
+}
+
+// Parse a time given in seconds, or in the form "(HH:)MM:SS(.mmm)", into seconds
+fn parse_time(time: &str) -> Option<f32> {
+    let parts: Vec<&str> = time.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+
+    let mut seconds = 0.0;
+    for (i, part) in parts.iter().enumerate() {
+        // Only the last part may have a fractional component
+        let value: f32 = if i == parts.len() - 1 {
+            part.parse().ok()?
+        } else {
+            part.parse::<u32>().ok()? as f32
+        };
+        if value < 0.0 || !value.is_finite() || (i > 0 && value >= 60.0) {
+            return None;
+        }
+        seconds = seconds * 60.0 + value;
+    }
+
+    Some(seconds)
+}
+
 // Convert milliseconds to a WebVTT timestamp (which has the format "(HH:)MM:SS.mmmm")
 fn timestamp(mseconds_total: i32) -> String {
     let hours = mseconds_total / (1000 * 60 * 60);
@@ -455,10 +2161,27 @@ fn timestamp(mseconds_total: i32) -> String {
     }
 }
 
-// Write a WebVTT file pointing to the thumbnail locations
-fn write_vtt(config: &Config, duration: f32) {
+// Returns start and end of the time range covered by the i-th thumbnail, in milliseconds
+fn cue_range(i: usize, config: &Config, duration: f32) -> (i32, i32) {
     let mseconds = (duration * 1_000.0) as i32;
+    let offset = (timestamp_offset(&config) * 1_000.0) as i32;
+    let from = offset + mseconds / (config.width as i32) * (i as i32);
+    let to = offset + mseconds / (config.width as i32) * ((i as i32) + 1);
+    (from, to)
+}
+
+// Returns the time in seconds which is added to all timestamps in the outputs. They are relative
+// to the beginning of the input file, unless relative timestamps were requested.
+fn timestamp_offset(config: &Config) -> f32 {
+    if config.relative_timestamps {
+        0.0
+    } else {
+        config.start
+    }
+}
 
+// Write a WebVTT file pointing to the thumbnail locations
+fn write_vtt(config: &Config, duration: f32, tiles: &[usize]) {
     let vtt_filename = config
         .vtt_filename
         .clone()
@@ -478,11 +2201,10 @@ fn write_vtt(config: &Config, duration: f32) {
         }
     }
 
-    for i in 0..config.width {
-        let from = mseconds / (config.width as i32) * (i as i32);
-        let to = mseconds / (config.width as i32) * ((i as i32) + 1);
+    for (i, &tile) in tiles.iter().enumerate() {
+        let (from, to) = cue_range(i, &config, duration);
 
-        let (file, x, y) = grid_position(i, &config);
+        let (file, x, y) = grid_position(tile, &config);
 
         let w = config.thumbnail_width;
         let h = config.thumbnail_height;
@@ -510,6 +2232,86 @@ fn write_vtt(config: &Config, duration: f32) {
     }
 }
 
+// Cut the thumbnails for a BIF file out of the thumbnail grids, and encode them as JPEGs. The BIF
+// contains one thumbnail every `bif_interval` seconds, so we pick the thumbnail of the column which
+// covers that point in time.
+fn bif_images(
+    config: &Config,
+    grids: &[frame::Frame],
+    tiles: &[usize],
+    duration: f32,
+) -> Vec<(u32, Vec<u8>)> {
+    let count = cmp::max((duration / config.bif_interval as f32).ceil() as usize, 1);
+
+    (0..count)
+        .map(|n| {
+            let time = (n * config.bif_interval as usize) as f32;
+            let i = cmp::min(
+                (config.width as f32 * time / duration) as usize,
+                config.width - 1,
+            );
+
+            let (file, x, y) = grid_position(tiles[i], &config);
+            let image = grids[file]
+                .crop(x, y, config.thumbnail_width, config.thumbnail_height)
+                .to_jpeg(40);
+            ((timestamp_offset(&config) + time).round() as u32, image)
+        })
+        .collect()
+}
+
+// Copy the thumbnails into new grids, leaving out those which look the same as an earlier one.
+// Returns the new grids, and for each thumbnail the index of the tile it is shown by, which takes
+// the place of the thumbnail's own index in `grid_position`.
+fn deduplicate_thumbnails(
+    config: &Config,
+    grids: &[frame::Frame],
+) -> (Vec<frame::Frame>, Vec<usize>) {
+    let crop = |i: usize| {
+        let (file, x, y) = grid_position(i, config);
+        grids[file].crop(x, y, config.thumbnail_width, config.thumbnail_height)
+    };
+
+    // The first thumbnail of each tile, its hash, and its luma
+    let mut unique: Vec<(usize, u64, Vec<f32>)> = vec![];
+    let mut tiles = vec![];
+    for i in 0..config.width {
+        let thumbnail = crop(i);
+        let thumbnail_hash = hash::dhash(&thumbnail);
+        let luma = thumbnail.luma();
+
+        // Only thumbnails with similar hashes need to be compared in full
+        let duplicate = unique.iter().position(|(_, other_hash, other_luma)| {
+            hash::distance(thumbnail_hash, *other_hash) <= DUPLICATE_DISTANCE
+                && qc::difference(&luma, other_luma) < DUPLICATE_DIFFERENCE
+        });
+        match duplicate {
+            Some(tile) => tiles.push(tile),
+            None => {
+                tiles.push(unique.len());
+                unique.push((i, thumbnail_hash, luma));
+            }
+        }
+    }
+
+    let (grid_columns, grid_rows) = grid_dimensions(config);
+    let grid_count = (unique.len() - 1) / (grid_columns * grid_rows) + 1;
+    let mut new_grids: Vec<frame::Frame> = (0..grid_count)
+        .map(|_| {
+            frame::Frame::new(
+                grid_columns * config.thumbnail_width,
+                grid_rows * config.thumbnail_height,
+            )
+        })
+        .collect();
+    for (tile, &(i, _, _)) in unique.iter().enumerate() {
+        let (file, x, y) = grid_position(tile, config);
+        new_grids[file].copy(&crop(i), x, y);
+    }
+
+    (new_grids, tiles)
+}
+
 // Die if `new_opt` has a value that collides with `existing`
 fn check_for_collision(existing: &str, new_opt: &Option<String>) {
     if let Some(new) = new_opt {
@@ -525,11 +2327,18 @@ fn check_for_collision(existing: &str, new_opt: &Option<String>) {
     }
 }
 
+// Returns how many thumbnails fit into a single thumbnail grid horizontally and vertically
+fn grid_dimensions(config: &Config) -> (usize, usize) {
+    (
+        MAX_GRID_WIDTH / config.thumbnail_width,
+        MAX_GRID_HEIGHT / config.thumbnail_height,
+    )
+}
+
 // For the i-th thumbnail, returns the number of the thumbnail grid it should be placed in, as well
 // as the x and y position in that file.
 fn grid_position(i: usize, config: &Config) -> (usize, usize, usize) {
-    let grid_columns = MAX_GRID_WIDTH / config.thumbnail_width;
-    let grid_rows = MAX_GRID_HEIGHT / config.thumbnail_height;
+    let (grid_columns, grid_rows) = grid_dimensions(&config);
 
     let file = i / (grid_columns * grid_rows);
     let pos = i % (grid_columns * grid_rows);
@@ -560,4 +2369,42 @@ mod tests {
         use crate::timestamp;
         assert_eq!(timestamp((13 + 60 * (30 + 60 * 2)) * 1000), "2:30:13.000");
     }
+
+    #[test]
+    fn test_output_base() {
+        use crate::output_base;
+        assert_eq!(output_base("dir/video.mp4"), "dir/video.mp4");
+        assert_eq!(
+            output_base("https://example.com/a/video.mp4?b=c"),
+            "video.mp4"
+        );
+        assert_eq!(output_base("https://example.com/"), "stream");
+        assert_eq!(output_base("rtsp://example.com"), "example.com");
+        assert_eq!(output_base("-"), "stdin");
+        assert_eq!(output_base("shots/frames/*.png"), "shots/frames");
+    }
+
+    #[test]
+    fn test_parse_framerate() {
+        use crate::parse_framerate;
+        assert_eq!(parse_framerate("24"), Some((24, 1)));
+        assert_eq!(parse_framerate("30000/1001"), Some((30000, 1001)));
+        assert_eq!(parse_framerate("0"), None);
+        assert_eq!(parse_framerate("25/0"), None);
+        assert_eq!(parse_framerate("23.976"), None);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        use crate::parse_time;
+        assert_eq!(parse_time("90"), Some(90.0));
+        assert_eq!(parse_time("1.5"), Some(1.5));
+        assert_eq!(parse_time("01:30"), Some(90.0));
+        assert_eq!(parse_time("2:30:13.5"), Some(9013.5));
+        assert_eq!(parse_time("1:60"), None);
+        assert_eq!(parse_time("1.5:00"), None);
+        assert_eq!(parse_time("-1"), None);
+        assert_eq!(parse_time("1:2:3:4"), None);
+        assert_eq!(parse_time(""), None);
+    }
 }
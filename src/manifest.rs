@@ -0,0 +1,299 @@
+use crate::audio;
+use crate::cuts;
+use crate::frame;
+use crate::hash;
+use crate::json;
+use crate::source;
+use crate::Config;
+use std::fs::File;
+use std::io::Write;
+
+// Write a JSON manifest describing the input file and all outputs of this run: the timeline, the
+// thumbnail grids, and the time range and position of every single thumbnail.
+pub fn write_to(
+    filename: &str,
+    config: &Config,
+    source: &source::VideoSource,
+    grids: &[frame::Frame],
+    tiles: &[usize],
+    cuts: &[cuts::Cut],
+) -> Result<(), String> {
+    let meta = &source.meta;
+    let crop = &source.crop;
+    let input = json::object(
+        &[
+            ("filename", json::string(&config.input_filename)),
+            ("duration", json::optional(meta.duration.map(json::number))),
+            ("width", meta.width.to_string()),
+            ("height", meta.height.to_string()),
+            ("aspect_ratio", json::number(meta.aspect_ratio)),
+            ("audio_only", meta.audio_only.to_string()),
+            ("offset", json::number(source.offset)),
+            ("parts", json::optional(parts(source))),
+            ("stream", meta.stream.to_string()),
+            (
+                "crop",
+                format!(
+                    "[{}, {}, {}, {}]",
+                    crop.top, crop.right, crop.bottom, crop.left
+                ),
+            ),
+        ],
+        1,
+    );
+
+    let timeline = config.timeline_filename.as_ref().map(|timeline_filename| {
+        json::object(
+            &[
+                ("filename", json::string(&timeline_filename)),
+                ("width", config.width.to_string()),
+                ("height", config.height.to_string()),
+            ],
+            1,
+        )
+    });
+
+    let range = json::object(
+        &[
+            ("start", json::number(source.start)),
+            ("duration", json::number(source.duration)),
+            (
+                "relative_timestamps",
+                config.relative_timestamps.to_string(),
+            ),
+        ],
+        1,
+    );
+
+    let thumbnails = if config.thumbnails_needed() {
+        Some(thumbnails(config, source.duration, grids, tiles))
+    } else {
+        None
+    };
+
+    // The audio strip is either a file of its own, or the bottom of the timeline
+    let audio = config.audio.map(|style| {
+        let (audio_filename, y) = match config.audio_filename {
+            Some(ref audio_filename) => (audio_filename.clone(), 0),
+            None => (
+                config.timeline_filename.clone().unwrap_or_default(),
+                config.height,
+            ),
+        };
+        let style = match style {
+            audio::Style::Waveform => "waveform",
+            audio::Style::Loudness => "loudness",
+        };
+        json::object(
+            &[
+                ("filename", json::string(&audio_filename)),
+                ("style", json::string(style)),
+                ("y", y.to_string()),
+                ("width", config.width.to_string()),
+                ("height", config.audio_height.to_string()),
+            ],
+            1,
+        )
+    });
+
+    let cuts = if config.cuts_needed() {
+        let positions = cuts::positions(config, source, cuts)
+            .iter()
+            .map(|&position| json::number(position))
+            .collect::<Vec<String>>();
+        Some(json::object(
+            &[
+                (
+                    "filename",
+                    json::optional(config.cuts_filename.as_ref().map(|f| json::string(f))),
+                ),
+                ("threshold", json::number(config.cut_threshold)),
+                ("positions", json::array(&positions, 2)),
+            ],
+            1,
+        ))
+    } else {
+        None
+    };
+
+    let qc = config.qc_filename.as_ref().map(|qc_filename| {
+        json::object(
+            &[
+                ("filename", json::string(&qc_filename)),
+                ("overlay", config.qc_overlay.to_string()),
+            ],
+            1,
+        )
+    });
+
+    let flash = config.flash_filename.as_ref().map(|flash_filename| {
+        json::object(
+            &[
+                ("filename", json::string(&flash_filename)),
+                ("markers", config.flash_markers.to_string()),
+            ],
+            1,
+        )
+    });
+
+    let palette = if config.palette_needed() {
+        Some(json::object(
+            &[
+                (
+                    "filename",
+                    json::optional(config.palette_filename.as_ref().map(|f| json::string(f))),
+                ),
+                (
+                    "swatches",
+                    json::optional(config.swatches_filename.as_ref().map(|f| json::string(f))),
+                ),
+                ("colors", config.palette_colors.to_string()),
+                (
+                    "interval",
+                    json::optional(config.palette_interval.map(json::number)),
+                ),
+            ],
+            1,
+        ))
+    } else {
+        None
+    };
+
+    let fingerprint = config
+        .fingerprint_filename
+        .as_ref()
+        .map(|fingerprint_filename| {
+            json::object(&[("filename", json::string(&fingerprint_filename))], 1)
+        });
+
+    let bif = config.bif_filename.as_ref().map(|bif_filename| {
+        json::object(
+            &[
+                ("filename", json::string(&bif_filename)),
+                ("interval", config.bif_interval.to_string()),
+            ],
+            1,
+        )
+    });
+
+    let manifest = json::object(
+        &[
+            ("input", input),
+            ("range", range),
+            ("timeline", json::optional(timeline)),
+            ("audio", json::optional(audio)),
+            ("cuts", json::optional(cuts)),
+            ("qc", json::optional(qc)),
+            ("flash", json::optional(flash)),
+            ("palette", json::optional(palette)),
+            ("fingerprint", json::optional(fingerprint)),
+            ("thumbnails", json::optional(thumbnails)),
+            ("bif", json::optional(bif)),
+        ],
+        0,
+    );
+
+    let mut file = match File::create(&filename) {
+        Ok(file) => file,
+        Err(e) => {
+            return Err(format!("Could not create '{}': {})", &filename, e));
+        }
+    };
+
+    match writeln!(&mut file, "{}", manifest) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+    }
+}
+
+// Describe the files which were concatenated, if there are several
+fn parts(source: &source::VideoSource) -> Option<String> {
+    if source.parts.is_empty() {
+        return None;
+    }
+
+    let parts = source
+        .parts
+        .iter()
+        .map(|part| {
+            json::object(
+                &[
+                    ("filename", json::string(&part.filename)),
+                    ("start", json::number(part.start)),
+                    ("inpoint", json::number(part.inpoint)),
+                    ("duration", json::number(part.duration)),
+                ],
+                3,
+            )
+        })
+        .collect::<Vec<String>>();
+    Some(json::array(&parts, 2))
+}
+
+// Describe the thumbnail grids, where each thumbnail is located in them, and its perceptual hash
+fn thumbnails(config: &Config, duration: f32, grids: &[frame::Frame], tiles: &[usize]) -> String {
+    let (grid_columns, grid_rows) = crate::grid_dimensions(&config);
+
+    // Grid files are only written together with the VTT file
+    let grid_files = if config.vtt_filename.is_some() {
+        grids
+            .iter()
+            .enumerate()
+            .map(|(i, grid)| {
+                json::object(
+                    &[
+                        ("filename", json::string(&crate::grid_filename(i, &config))),
+                        ("width", grid.width.to_string()),
+                        ("height", grid.height.to_string()),
+                        ("columns", grid_columns.to_string()),
+                        ("rows", grid_rows.to_string()),
+                    ],
+                    3,
+                )
+            })
+            .collect::<Vec<String>>()
+    } else {
+        vec![]
+    };
+
+    let cues = (0..config.width)
+        .map(|i| {
+            let (from, to) = crate::cue_range(i, &config, duration);
+            let (file, x, y) = crate::grid_position(tiles[i], &config);
+            let hash = grids.get(file).map(|grid| {
+                let thumbnail = grid.crop(x, y, config.thumbnail_width, config.thumbnail_height);
+                json::string(&hash::hex(hash::dhash(&thumbnail)))
+            });
+            json::object(
+                &[
+                    ("from", json::number(from as f32 / 1000.0)),
+                    ("to", json::number(to as f32 / 1000.0)),
+                    ("grid", file.to_string()),
+                    (
+                        "xywh",
+                        format!(
+                            "[{}, {}, {}, {}]",
+                            x, y, config.thumbnail_width, config.thumbnail_height
+                        ),
+                    ),
+                    ("hash", json::optional(hash)),
+                ],
+                3,
+            )
+        })
+        .collect::<Vec<String>>();
+
+    json::object(
+        &[
+            (
+                "vtt",
+                json::optional(config.vtt_filename.as_ref().map(|f| json::string(f))),
+            ),
+            ("width", config.thumbnail_width.to_string()),
+            ("height", config.thumbnail_height.to_string()),
+            ("grids", json::array(&grid_files, 2)),
+            ("cues", json::array(&cues, 2)),
+        ],
+        1,
+    )
+}
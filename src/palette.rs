@@ -0,0 +1,436 @@
+use crate::cuts;
+use crate::frame;
+use crate::json;
+use crate::source;
+use crate::Config;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::Write;
+
+// Colors are counted with this many bits per channel. The colors of a palette are the averages of
+// the pixels they stand for, so they are more precise than that.
+const BITS: usize = 4;
+const BINS: usize = 1 << (3 * BITS);
+
+// Share of the swatch image's height which shows the palette of the whole input. Below it, each
+// column shows the palette of the segment it belongs to.
+const OVERALL_HEIGHT: f32 = 0.25;
+
+// How many pixels had a color of a quantized bin, and the sums of their exact values, as RGB
+#[derive(Clone, Copy, Default)]
+struct Bin {
+    count: u64,
+    sums: [u64; 3],
+}
+
+impl Bin {
+    // Returns the average color of the pixels in this bin
+    fn mean(&self) -> [f64; 3] {
+        let count = self.count.max(1) as f64;
+        [
+            self.sums[0] as f64 / count,
+            self.sums[1] as f64 / count,
+            self.sums[2] as f64 / count,
+        ]
+    }
+}
+
+// A color of a palette, and the share of the pixels which it stands for
+pub struct Swatch {
+    pub color: [u8; 3],
+    pub share: f32,
+}
+
+// A part of the input from `start` to `end` seconds, like the frames' timestamps, and its palette
+pub struct Segment {
+    pub start: f32,
+    pub end: f32,
+    pub palette: Vec<Swatch>,
+}
+
+// Finds the most common colors of the input, and of each of its segments, which are either the
+// scenes between cuts, or of a fixed length. Frames have to be added in order.
+pub struct Extractor {
+    // Number of colors in each palette
+    colors: usize,
+    // Length of the segments in seconds, or `None` if they are the scenes
+    interval: Option<f32>,
+    // Position where the first segment starts
+    start: f32,
+    // Position where the current segment starts, and its colors
+    segment_start: f32,
+    segment: Vec<Bin>,
+    // Colors of the whole input, except for the current segment
+    overall: Vec<Bin>,
+    segments: Vec<Segment>,
+}
+
+impl Extractor {
+    // Initializes an extractor for palettes of `colors` colors, for segments of `interval`
+    // seconds, or for scenes, starting at `start`
+    pub fn new(start: f32, colors: usize, interval: Option<f32>) -> Self {
+        Self {
+            colors,
+            interval,
+            start,
+            segment_start: start,
+            segment: vec![Bin::default(); BINS],
+            overall: vec![Bin::default(); BINS],
+            segments: vec![],
+        }
+    }
+
+    // Count the colors of `frame`. A new segment starts with it if it is in the next interval,
+    // or, if the segments are the scenes, if it is the first frame after the last of the `cuts`.
+    pub fn add(&mut self, frame: &frame::Frame, cuts: &[cuts::Cut]) {
+        let pts = frame.pts.expect("Could not get PTS of frame for palette");
+
+        let boundary = match self.interval {
+            Some(interval) => Some(self.start + ((pts - self.start) / interval).floor() * interval),
+            None => cuts.last().map(|cut| cut.position),
+        };
+        match boundary {
+            Some(boundary) if boundary > self.segment_start => self.finish_segment(boundary),
+            _ => {}
+        }
+
+        let map = frame
+            .buffer
+            .map_readable()
+            .expect("Could not get readable map of buffer for palette");
+        for pixel in map.as_slice().chunks_exact(4) {
+            // Frames are stored as BGRx
+            let (r, g, b) = (pixel[2], pixel[1], pixel[0]);
+            let bin = &mut self.segment[bin_index(r, g, b)];
+            bin.count += 1;
+            bin.sums[0] += u64::from(r);
+            bin.sums[1] += u64::from(g);
+            bin.sums[2] += u64::from(b);
+        }
+    }
+
+    // End the current segment at `end`, and start the next one there. Segments without any
+    // frames are left out.
+    fn finish_segment(&mut self, end: f32) {
+        if self.segment.iter().any(|bin| bin.count > 0) {
+            self.segments.push(Segment {
+                start: self.segment_start,
+                end,
+                palette: palette(&self.segment, self.colors),
+            });
+
+            for (total, bin) in self.overall.iter_mut().zip(self.segment.iter_mut()) {
+                total.count += bin.count;
+                for channel in 0..3 {
+                    total.sums[channel] += bin.sums[channel];
+                }
+                *bin = Bin::default();
+            }
+        }
+        self.segment_start = end;
+    }
+}
+
+// The palettes of the whole input and of its segments
+pub struct Report {
+    pub palette: Vec<Swatch>,
+    pub segments: Vec<Segment>,
+}
+
+impl Report {
+    // Finish the last segment found by the `extractor` at the end of `source`
+    pub fn new(mut extractor: Extractor, source: &source::VideoSource) -> Self {
+        extractor.finish_segment(source.start + source.duration);
+
+        Self {
+            palette: palette(&extractor.overall, extractor.colors),
+            segments: extractor.segments,
+        }
+    }
+}
+
+// Returns the index of the bin which the color `r`, `g`, `b` is counted in
+fn bin_index(r: u8, g: u8, b: u8) -> usize {
+    let quantize = |value: u8| usize::from(value) >> (8 - BITS);
+    (quantize(r) << (2 * BITS)) | (quantize(g) << BITS) | quantize(b)
+}
+
+// Returns up to `count` colors which represent the pixels counted in `bins` best, most common
+// first. They are found by median cut: starting with a box around all colors, the box whose
+// pixels differ the most from its average color is split in two at the median of its widest
+// channel, until there are enough boxes.
+fn palette(bins: &[Bin], count: usize) -> Vec<Swatch> {
+    let total: u64 = bins.iter().map(|bin| bin.count).sum();
+    if total == 0 {
+        return vec![];
+    }
+
+    let mut boxes: Vec<Vec<Bin>> = vec![bins.iter().filter(|bin| bin.count > 0).cloned().collect()];
+    while boxes.len() < count {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(i, colors)| (i, error(colors)))
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        let mut colors = match widest {
+            Some((i, _)) => boxes.swap_remove(i),
+            None => break,
+        };
+
+        // Sort along the channel in which the colors spread the most
+        let range = |channel: usize| {
+            let values = colors.iter().map(|bin| bin.mean()[channel]);
+            values.clone().fold(0.0, f64::max) - values.fold(255.0, f64::min)
+        };
+        let channel = (0..3)
+            .max_by(|&a, &b| range(a).partial_cmp(&range(b)).unwrap_or(Ordering::Equal))
+            .unwrap_or(0);
+        colors.sort_by(|a, b| {
+            a.mean()[channel]
+                .partial_cmp(&b.mean()[channel])
+                .unwrap_or(Ordering::Equal)
+        });
+
+        // Split where half of the pixels are on either side, keeping both halves non-empty
+        let population: u64 = colors.iter().map(|bin| bin.count).sum();
+        let mut seen = 0;
+        let mut split = colors.len() - 1;
+        for (i, bin) in colors.iter().enumerate() {
+            seen += bin.count;
+            if 2 * seen >= population {
+                split = i + 1;
+                break;
+            }
+        }
+        let split = split.max(1).min(colors.len() - 1);
+
+        let other = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(other);
+    }
+
+    let mut swatches: Vec<Swatch> = boxes
+        .iter()
+        .map(|colors| {
+            let merged = merge(colors);
+            let mean = merged.mean();
+            Swatch {
+                color: [
+                    mean[0].round() as u8,
+                    mean[1].round() as u8,
+                    mean[2].round() as u8,
+                ],
+                share: merged.count as f32 / total as f32,
+            }
+        })
+        .collect();
+    swatches.sort_by(|a, b| b.share.partial_cmp(&a.share).unwrap_or(Ordering::Equal));
+    swatches
+}
+
+// Returns a bin which counts all pixels of `bins`
+fn merge(bins: &[Bin]) -> Bin {
+    bins.iter().fold(Bin::default(), |mut merged, bin| {
+        merged.count += bin.count;
+        for channel in 0..3 {
+            merged.sums[channel] += bin.sums[channel];
+        }
+        merged
+    })
+}
+
+// Returns the sum of the squared distances of the pixels in `bins` from their average color
+fn error(bins: &[Bin]) -> f64 {
+    let mean = merge(bins).mean();
+    bins.iter()
+        .map(|bin| {
+            let color = bin.mean();
+            let distance: f64 = (0..3).map(|c| (color[c] - mean[c]).powi(2)).sum();
+            bin.count as f64 * distance
+        })
+        .sum()
+}
+
+// Render `palette` as a JSON array
+fn swatches(palette: &[Swatch], indent: usize) -> String {
+    let swatches = palette
+        .iter()
+        .map(|swatch| {
+            json::object(
+                &[
+                    ("color", json::string(&hex(swatch.color))),
+                    ("share", swatch.share.to_string()),
+                ],
+                indent + 1,
+            )
+        })
+        .collect::<Vec<String>>();
+    json::array(&swatches, indent)
+}
+
+// Returns `color` in the form #rrggbb
+fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+// Write the `report` to `filename` as JSON. Positions are offset like all other timestamps.
+pub fn write_to(
+    filename: &str,
+    config: &Config,
+    source: &source::VideoSource,
+    report: &Report,
+) -> Result<(), String> {
+    let offset = crate::timestamp_offset(config) - source.start;
+    let segments = report
+        .segments
+        .iter()
+        .map(|segment| {
+            json::object(
+                &[
+                    ("start", (segment.start + offset).to_string()),
+                    ("end", (segment.end + offset).to_string()),
+                    ("duration", (segment.end - segment.start).to_string()),
+                    (
+                        "timestamp",
+                        json::string(&crate::timestamp(
+                            ((segment.start + offset) * 1000.0).round() as i32,
+                        )),
+                    ),
+                    ("palette", swatches(&segment.palette, 3)),
+                ],
+                2,
+            )
+        })
+        .collect::<Vec<String>>();
+
+    let content = json::object(
+        &[
+            ("input", json::string(&config.input_filename)),
+            ("start", crate::timestamp_offset(config).to_string()),
+            ("duration", source.duration.to_string()),
+            (
+                "interval",
+                json::optional(config.palette_interval.map(|interval| interval.to_string())),
+            ),
+            ("palette", swatches(&report.palette, 1)),
+            ("segments", json::array(&segments, 1)),
+        ],
+        0,
+    );
+
+    let mut file = match File::create(filename) {
+        Ok(file) => file,
+        Err(e) => {
+            return Err(format!("Could not create '{}': {})", &filename, e));
+        }
+    };
+
+    match writeln!(&mut file, "{}", content) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+    }
+}
+
+// Draw the palettes of the `report` as an image of the same size as the visual timeline. Along
+// the top, the palette of the whole input is shown as horizontal stripes, and below it, each
+// column shows the palette of its segment as vertical stripes, both as wide as their shares.
+pub fn draw_swatches(
+    config: &Config,
+    source: &source::VideoSource,
+    report: &Report,
+) -> frame::Frame {
+    let mut image = frame::Frame::new(config.width, config.height);
+
+    {
+        let buffer = image
+            .buffer
+            .get_mut()
+            .expect("Could not get mutable buffer for swatches");
+        let mut data = buffer
+            .map_writable()
+            .expect("Could not get writable map of buffer for swatches");
+
+        let overall_height = ((config.height as f32 * OVERALL_HEIGHT).round() as usize)
+            .max(1)
+            .min(config.height);
+        let overall = stripes(&report.palette, config.width);
+        let empty = vec![];
+
+        for (x, &overall_color) in overall.iter().enumerate() {
+            // The segment which contains the middle of the column
+            let time = source.start + source.duration * (x as f32 + 0.5) / config.width as f32;
+            let palette = report
+                .segments
+                .iter()
+                .find(|segment| segment.start <= time && time < segment.end)
+                .map_or(&empty, |segment| &segment.palette);
+            let column = stripes(palette, config.height - overall_height);
+
+            for y in 0..config.height {
+                let color = if y < overall_height {
+                    overall_color
+                } else {
+                    column[y - overall_height]
+                };
+
+                // Frames are stored as BGRx
+                let i = (y * config.width + x) * 4;
+                data[i] = color[2];
+                data[i + 1] = color[1];
+                data[i + 2] = color[0];
+                data[i + 3] = 255;
+            }
+        }
+    }
+
+    image
+}
+
+// Returns the colors of `length` pixels, which are divided among the colors of `palette`
+// according to their shares. Pixels not covered by any color are black.
+fn stripes(palette: &[Swatch], length: usize) -> Vec<[u8; 3]> {
+    let mut pixels = vec![[0; 3]; length];
+    let mut from = 0.0;
+    for swatch in palette {
+        let to = from + swatch.share * length as f32;
+        let range = (from.round() as usize).min(length)..(to.round() as usize).min(length);
+        for pixel in &mut pixels[range] {
+            *pixel = swatch.color;
+        }
+        from = to;
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::palette::{bin_index, palette, Bin, BINS};
+
+    #[test]
+    fn test_palette() {
+        // Three quarters dark red, and one quarter of two similar light blues
+        let mut bins = vec![Bin::default(); BINS];
+        for (color, count) in &[
+            ([200, 10, 10], 300),
+            ([10, 100, 240], 50),
+            ([20, 110, 250], 50),
+        ] {
+            let bin = &mut bins[bin_index(color[0], color[1], color[2])];
+            bin.count += count;
+            for (sum, &value) in bin.sums.iter_mut().zip(color) {
+                *sum += count * u64::from(value);
+            }
+        }
+
+        let swatches = palette(&bins, 2);
+        assert_eq!(swatches.len(), 2);
+        assert_eq!(swatches[0].color, [200, 10, 10]);
+        assert_eq!(swatches[0].share, 0.75);
+        assert_eq!(swatches[1].color, [15, 105, 245]);
+
+        // There are only three colors to choose from
+        assert_eq!(palette(&bins, 5).len(), 3);
+        assert!(palette(&vec![Bin::default(); BINS], 5).is_empty());
+    }
+}
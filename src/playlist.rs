@@ -0,0 +1,282 @@
+use std::cmp;
+use std::fs;
+use std::path::Path;
+
+// If `filename` is a list of input files, returns the files it contains, in order. Supported are
+// M3U playlists (".m3u" and ".m3u8"), concat lists as used by FFmpeg (".ffconcat", or ".txt" if
+// the file looks like one), which contain one line like `file 'part1.mp4'` per file, and EDLs in
+// the CMX 3600 format (".edl"), whose events name their files in `* FROM CLIP NAME:` comments.
+//
+// Relative paths in the list are relative to the directory the list is in.
+pub fn read(filename: &str) -> Result<Option<Vec<Entry>>, String> {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "m3u" | "m3u8" | "ffconcat" | "txt" | "edl" => {}
+        _ => return Ok(None),
+    }
+
+    let content = match fs::read_to_string(filename) {
+        Ok(content) => content,
+        Err(e) => return Err(format!("Could not read '{}': {}", filename, e)),
+    };
+
+    let entries = match extension.as_str() {
+        "m3u" | "m3u8" => whole_files(parse_m3u(&content)),
+        "edl" => parse_edl(&content),
+        // Other text files are left to GStreamer
+        "txt" if !is_concat(&content) => return Ok(None),
+        _ => whole_files(parse_concat(&content)),
+    };
+    if entries.is_empty() {
+        return Err(format!(
+            "The list '{}' does not contain any files.",
+            filename
+        ));
+    }
+
+    let directory = Path::new(filename)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    Ok(Some(
+        entries
+            .into_iter()
+            .map(|(entry, edit)| (resolve(directory, &entry), edit))
+            .collect(),
+    ))
+}
+
+// A file in a list, and the range of it which is used, or `None` for the whole file
+pub type Entry = (String, Option<Edit>);
+
+// Which range of a file an EDL event uses, from its source in point to its source out point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edit {
+    pub from: Timecode,
+    pub to: Timecode,
+}
+
+// A position in a file, as a timecode like `00:01:10:12`. Drop-frame timecodes separate the frames
+// with a semicolon instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timecode {
+    // Hours, minutes and seconds, in seconds
+    seconds: u32,
+    // Frames in addition to `seconds`
+    frames: u32,
+    drop_frame: bool,
+}
+
+impl Timecode {
+    fn parse(value: &str) -> Option<Timecode> {
+        let fields = value
+            .replace(';', ":")
+            .split(':')
+            .map(|field| field.parse().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        if fields.len() != 4 {
+            return None;
+        }
+        Some(Timecode {
+            seconds: fields[0] * 3600 + fields[1] * 60 + fields[2],
+            frames: fields[3],
+            drop_frame: value.contains(';'),
+        })
+    }
+
+    // Returns the position in seconds, for a file with the given `framerate`. Timecodes count
+    // whole frames per second, so at 29.97 FPS, they count 30 frames per second instead.
+    pub fn to_seconds(self, framerate: f32) -> f32 {
+        let nominal = cmp::max(framerate.round() as u32, 1);
+        let mut frames = self.seconds * nominal + self.frames;
+
+        // Drop-frame timecodes skip 2 frame numbers at 30 FPS (or 4 at 60 FPS) at the start of
+        // each minute, except for every tenth minute
+        if self.drop_frame {
+            let minutes = self.seconds / 60;
+            frames -= nominal / 15 * (minutes - minutes / 10);
+        }
+        frames as f32 / framerate
+    }
+}
+
+// Use all of each file
+fn whole_files(entries: Vec<String>) -> Vec<Entry> {
+    entries.into_iter().map(|entry| (entry, None)).collect()
+}
+
+// Returns the entries of an M3U playlist. Lines starting with "#" are comments or directives.
+fn parse_m3u(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+// Returns the entries of a concat list. Other directives, like "duration" or "inpoint", are
+// ignored.
+fn parse_concat(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("file "))
+        .map(|line| unquote(line["file ".len()..].trim()))
+        .collect()
+}
+
+// Whether `content` looks like a concat list: its first line which isn't a comment is either the
+// header or a file
+fn is_concat(content: &str) -> bool {
+    match content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+    {
+        Some(line) => line.starts_with("ffconcat version") || line.starts_with("file "),
+        None => false,
+    }
+}
+
+// Returns the files of an EDL's events, with the ranges given by their source timecodes. An event
+// which has both a video and an audio track only adds its file once.
+fn parse_edl(content: &str) -> Vec<Entry> {
+    let mut entries = vec![];
+    let mut event = None;
+    let mut added = None;
+    for line in content.lines().map(str::trim) {
+        let first = line.split_whitespace().next().unwrap_or("");
+        let mut fields = line.splitn(2, ':');
+        if !first.is_empty() && first.chars().all(|c| c.is_ascii_digit()) {
+            // The first two timecodes are the source in and out points, the others are where the
+            // event is placed in the edited video
+            let mut timecodes = line.split_whitespace().filter_map(Timecode::parse);
+            let edit = match (timecodes.next(), timecodes.next()) {
+                (Some(from), Some(to)) => Some(Edit { from, to }),
+                _ => None,
+            };
+            event = Some((String::from(first), edit));
+        } else if let (Some("* FROM CLIP NAME"), Some(clip)) = (fields.next(), fields.next()) {
+            let clip = clip.trim();
+            let (number, edit) = match event {
+                Some((ref number, edit)) if !clip.is_empty() => (number, edit),
+                _ => continue,
+            };
+            if added.as_ref() == Some(number) {
+                continue;
+            }
+            added = Some(number.clone());
+            entries.push((String::from(clip), edit));
+        }
+    }
+    entries
+}
+
+// Remove single quotes around `value`, and resolve the escape sequence `'\''` for quotes inside
+fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        value[1..value.len() - 1].replace("'\\''", "'")
+    } else {
+        String::from(value)
+    }
+}
+
+// Make relative paths in a list relative to its `directory`. URIs and absolute paths are kept.
+fn resolve(directory: &Path, entry: &str) -> String {
+    if crate::source::is_uri(entry) || Path::new(entry).is_absolute() {
+        String::from(entry)
+    } else {
+        directory.join(entry).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_parse_m3u() {
+        use crate::playlist::parse_m3u;
+        assert_eq!(
+            parse_m3u("#EXTM3U\n#EXTINF:10,Part 1\npart1.mp4\n\n  part2.mp4 \n"),
+            vec!["part1.mp4", "part2.mp4"]
+        );
+    }
+
+    #[test]
+    fn test_parse_concat() {
+        use crate::playlist::parse_concat;
+        assert_eq!(
+            parse_concat(
+                "ffconcat version 1.0\nfile 'part 1.mp4'\nduration 10\nfile 'it'\\''s.mp4'\n"
+            ),
+            vec!["part 1.mp4", "it's.mp4"]
+        );
+    }
+
+    #[test]
+    fn test_is_concat() {
+        use crate::playlist::is_concat;
+        assert!(is_concat("# Parts\nfile 'part1.mp4'\n"));
+        assert!(is_concat("ffconcat version 1.0\n"));
+        assert!(!is_concat("Some notes\nfile 'part1.mp4'\n"));
+    }
+
+    #[test]
+    fn test_parse_edl() {
+        use crate::playlist::parse_edl;
+        assert_eq!(
+            parse_edl(
+                "TITLE: Episode\nFCM: NON-DROP FRAME\n\n\
+                 001  AX       V     C        00:00:00:00 00:00:10:00 00:00:00:00 00:00:10:00\n\
+                 * FROM CLIP NAME: part1.mp4\n\
+                 001  AX       A     C        00:00:00:00 00:00:10:00 00:00:00:00 00:00:10:00\n\
+                 * FROM CLIP NAME: part1.mp4\n\n\
+                 002  AX       V     C        00:00:00:00 00:00:05:00 00:00:10:00 00:00:15:00\n\
+                 * FROM CLIP NAME: part2.mp4\n\n\
+                 003  AX       V     C        00:00:05:00 00:00:09:00 00:00:15:00 00:00:19:00\n\
+                 * FROM CLIP NAME: part2.mp4\n"
+            ),
+            vec![
+                (
+                    String::from("part1.mp4"),
+                    Some(edit("00:00:00:00", "00:00:10:00"))
+                ),
+                (
+                    String::from("part2.mp4"),
+                    Some(edit("00:00:00:00", "00:00:05:00"))
+                ),
+                (
+                    String::from("part2.mp4"),
+                    Some(edit("00:00:05:00", "00:00:09:00"))
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timecode() {
+        use crate::playlist::Timecode;
+        let seconds = |value, framerate| {
+            Timecode::parse(value)
+                .expect("Could not parse timecode")
+                .to_seconds(framerate)
+        };
+        assert!((seconds("00:01:10:12", 25.0) - 70.48).abs() < 0.001);
+        assert!((seconds("00:00:01:00", 30000.0 / 1001.0) - 1.001).abs() < 0.001);
+        assert!((seconds("00:10:00;00", 30000.0 / 1001.0) - 600.0).abs() < 0.001);
+        assert_eq!(Timecode::parse("00:01:10"), None);
+        assert_eq!(Timecode::parse("AX"), None);
+    }
+
+    fn edit(from: &str, to: &str) -> crate::playlist::Edit {
+        use crate::playlist::{Edit, Timecode};
+        Edit {
+            from: Timecode::parse(from).expect("Could not parse timecode"),
+            to: Timecode::parse(to).expect("Could not parse timecode"),
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::audio;
+use crate::frame;
+use crate::json;
+use crate::source;
+use crate::Config;
+use std::fs::File;
+use std::io::Write;
+
+// Pixels whose luma is below this value count as black
+const BLACK_LUMA: f32 = 32.0;
+
+// Frames count as black if at least this share of their pixels is black
+const BLACK_RATIO: f32 = 0.98;
+
+// Consecutive frames count as identical if their luma differs by less than this on average,
+// which leaves some room for compression noise
+const FROZEN_DIFFERENCE: f32 = 1.0;
+
+// Audio whose peak level stays below this many dBFS counts as silent
+const SILENCE_LEVEL: f32 = -60.0;
+
+// Minimum durations of the reported spans, in seconds
+const MIN_BLACK: f32 = 0.5;
+const MIN_FROZEN: f32 = 2.0;
+const MIN_SILENCE: f32 = 2.0;
+
+// Color which the problematic spans are highlighted with on the timeline, as RGB, and how
+// strongly it covers the frames
+const HIGHLIGHT: [u8; 3] = [255, 0, 200];
+const HIGHLIGHT_OPACITY: f32 = 0.6;
+
+// A range of the video from `start` to `end` seconds, like the frames' timestamps
+pub struct Span {
+    pub start: f32,
+    pub end: f32,
+}
+
+// Collects the spans in which consecutive frames have a certain property
+#[derive(Default)]
+struct Spans {
+    // Where the current span started, if there is one
+    start: Option<f32>,
+    spans: Vec<Span>,
+}
+
+impl Spans {
+    // Continue or end the current span at `pts`, depending on whether the frame there is
+    // `inside` of it, and keep it if it lasted at least `min_duration` seconds
+    fn update(&mut self, inside: bool, pts: f32, min_duration: f32) {
+        match (self.start, inside) {
+            (None, true) => self.start = Some(pts),
+            (Some(start), false) => {
+                if pts - start >= min_duration {
+                    self.spans.push(Span { start, end: pts });
+                }
+                self.start = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Finds spans of black frames and of frozen frames, for quality control of deliveries. Frames have
+// to be added in order.
+#[derive(Default)]
+pub struct Checker {
+    // Luma of the previous frame, and its position
+    previous: Option<(Vec<f32>, f32)>,
+    black: Spans,
+    frozen: Spans,
+}
+
+impl Checker {
+    // Check whether `frame` is black, or identical to the previous one
+    pub fn add(&mut self, frame: &frame::Frame) {
+        let pts = frame.pts.expect("Could not get PTS of frame for QC");
+        let luma = frame.luma();
+
+        let black_pixels = luma.iter().filter(|&&y| y < BLACK_LUMA).count();
+        let black = black_pixels as f32 >= BLACK_RATIO * luma.len() as f32;
+        self.black.update(black, pts, MIN_BLACK);
+
+        // Black frames are always identical, but they are already reported as such. A frozen span
+        // starts at the first of the identical frames.
+        let (frozen, start) = match self.previous {
+            Some((ref previous, previous_pts)) if !black => (
+                difference(previous, &luma) < FROZEN_DIFFERENCE,
+                previous_pts,
+            ),
+            _ => (false, pts),
+        };
+        self.frozen
+            .update(frozen, if frozen { start } else { pts }, MIN_FROZEN);
+
+        self.previous = Some((luma, pts));
+    }
+}
+
+// The problematic spans of a video
+pub struct Report {
+    pub black: Vec<Span>,
+    pub frozen: Vec<Span>,
+    // Spans of silence, or `None` if the audio track wasn't analyzed, or if there is none
+    pub silence: Option<Vec<Span>>,
+}
+
+impl Report {
+    // Finish the spans found by the `checker`, and find the silent ones in the audio track of
+    // `source`, if it was measured. Without any audio samples, nothing is known about silence.
+    pub fn new(mut checker: Checker, source: &source::VideoSource) -> Self {
+        let end = source.start + source.duration;
+        checker.black.update(false, end, MIN_BLACK);
+        checker.frozen.update(false, end, MIN_FROZEN);
+
+        let silence = source.audio.as_ref().and_then(|levels| {
+            let levels = levels.lock().expect("Could not lock audio levels for QC");
+            if levels.is_empty() {
+                return None;
+            }
+            Some(
+                levels
+                    .silences(source.start, end, SILENCE_LEVEL, MIN_SILENCE)
+                    .into_iter()
+                    .map(|(start, end)| Span { start, end })
+                    .collect(),
+            )
+        });
+
+        Self {
+            black: checker.black.spans,
+            frozen: checker.frozen.spans,
+            silence,
+        }
+    }
+
+    // All spans, regardless of their kind
+    fn spans(&self) -> impl Iterator<Item = &Span> {
+        self.black
+            .iter()
+            .chain(self.frozen.iter())
+            .chain(self.silence.iter().flatten())
+    }
+}
+
+// Returns the average absolute difference between two images of luma values
+pub fn difference(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return f32::INFINITY;
+    }
+
+    a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum::<f32>() / a.len() as f32
+}
+
+// Write the `report` to `filename` as JSON. Positions are offset like all other timestamps.
+pub fn write_to(
+    filename: &str,
+    config: &Config,
+    source: &source::VideoSource,
+    report: &Report,
+) -> Result<(), String> {
+    let offset = crate::timestamp_offset(config) - source.start;
+    let content = json::object(
+        &[
+            ("input", json::string(&config.input_filename)),
+            ("start", crate::timestamp_offset(config).to_string()),
+            ("duration", source.duration.to_string()),
+            ("black", spans(&report.black, offset)),
+            ("frozen", spans(&report.frozen, offset)),
+            (
+                "silence",
+                json::optional(
+                    report
+                        .silence
+                        .as_ref()
+                        .map(|silence| spans(silence, offset)),
+                ),
+            ),
+        ],
+        0,
+    );
+
+    let mut file = match File::create(filename) {
+        Ok(file) => file,
+        Err(e) => {
+            return Err(format!("Could not create '{}': {})", &filename, e));
+        }
+    };
+
+    match writeln!(&mut file, "{}", content) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write to '{}': {})", &filename, e)),
+    }
+}
+
+// Render `spans` as a JSON array for a report, with their positions shifted by `offset` seconds
+pub fn spans(spans: &[Span], offset: f32) -> String {
+    let spans = spans
+        .iter()
+        .map(|span| {
+            json::object(
+                &[
+                    ("start", (span.start + offset).to_string()),
+                    ("end", (span.end + offset).to_string()),
+                    ("duration", (span.end - span.start).to_string()),
+                    (
+                        "timestamp",
+                        json::string(&crate::timestamp(
+                            ((span.start + offset) * 1000.0).round() as i32
+                        )),
+                    ),
+                ],
+                2,
+            )
+        })
+        .collect::<Vec<String>>();
+    json::array(&spans, 1)
+}
+
+// Tint the columns of the visual `timeline` which overlap any of the spans in the `report`
+pub fn draw_overlay(
+    timeline: &mut frame::Frame,
+    config: &Config,
+    source: &source::VideoSource,
+    report: &Report,
+) {
+    let buffer = timeline
+        .buffer
+        .get_mut()
+        .expect("Could not get mutable buffer for QC overlay");
+    let mut data = buffer
+        .map_writable()
+        .expect("Could not get writable map of buffer for QC overlay");
+
+    for x in 0..config.width {
+        let from = source.start + source.duration * x as f32 / config.width as f32;
+        let to = source.start + source.duration * (x + 1) as f32 / config.width as f32;
+        if !report
+            .spans()
+            .any(|span| span.start < to && span.end > from)
+        {
+            continue;
+        }
+
+        for y in 0..config.height {
+            let i = (y * config.width + x) * 4;
+            // Frames are stored as BGRx
+            let color = audio::mix(
+                [data[i + 2], data[i + 1], data[i]],
+                HIGHLIGHT,
+                HIGHLIGHT_OPACITY,
+            );
+            data[i] = color[2];
+            data[i + 1] = color[1];
+            data[i + 2] = color[0];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_spans() {
+        use crate::qc::Spans;
+
+        // Frames every 0.5 seconds, of which the ones from 1 to 2.5 and at 4 are inside
+        let mut spans = Spans::default();
+        for (i, inside) in [false, false, true, true, true, true, false, false, true]
+            .iter()
+            .enumerate()
+        {
+            spans.update(*inside, i as f32 * 0.5, 1.0);
+        }
+        spans.update(false, 4.5, 1.0);
+
+        assert_eq!(spans.spans.len(), 1);
+        assert_eq!((spans.spans[0].start, spans.spans[0].end), (1.0, 3.0));
+    }
+}
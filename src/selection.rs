@@ -0,0 +1,185 @@
+use crate::cuts;
+use crate::frame;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+// Frames closer to a cut than this many seconds are avoided, because they often still show the
+// end of the previous shot, or are part of a transition
+const CUT_MARGIN: f32 = 0.25;
+
+// Frames whose average luma is below this value are considered black
+const MIN_BRIGHTNESS: f32 = 24.0;
+
+// Frames whose luma has a smaller standard deviation than this are considered blank, like the
+// middle of a fade, or a title card without a title
+const MIN_CONTRAST: f32 = 8.0;
+
+// What makes a frame a good thumbnail
+struct Score {
+    // Position of the frame in seconds
+    pts: f32,
+    // Whether the frame is black, or otherwise shows almost nothing
+    blank: bool,
+    // Variance of the Laplacian of the frame's luma, which is higher for sharper frames
+    sharpness: f32,
+}
+
+// A thumbnail which might be picked for its column
+struct Candidate {
+    score: Score,
+    thumbnail: frame::Frame,
+}
+
+// Picks the most representative thumbnail of each column, out of all frames which belong to it:
+// one which isn't next to a cut, isn't black or blank, and is as sharp as possible. Thumbnails
+// have to be added in order.
+#[derive(Default)]
+pub struct Selector {
+    // Columns whose thumbnail wasn't picked yet, with their candidates
+    pending: VecDeque<(usize, Vec<Candidate>)>,
+}
+
+impl Selector {
+    // Add the `thumbnail` of the frame at `pts` as a candidate for the i-th column
+    pub fn add(&mut self, i: usize, pts: f32, thumbnail: frame::Frame) {
+        let score = score(&thumbnail, pts);
+        let candidate = Candidate { score, thumbnail };
+
+        match self.pending.back_mut() {
+            Some((column, candidates)) if *column == i => candidates.push(candidate),
+            _ => self.pending.push_back((i, vec![candidate])),
+        }
+    }
+
+    // Pick the thumbnails of the columns whose candidates are all far enough before `pts` that
+    // all cuts near them are known, or of all columns if `pts` is `None`. Returns the columns'
+    // indices and their thumbnails.
+    pub fn finish(&mut self, pts: Option<f32>, cuts: &[cuts::Cut]) -> Vec<(usize, frame::Frame)> {
+        let mut picked = vec![];
+
+        while let Some((_, candidates)) = self.pending.front() {
+            let last = candidates
+                .last()
+                .expect("Could not get last thumbnail candidate")
+                .score
+                .pts;
+            match pts {
+                Some(pts) if last + CUT_MARGIN >= pts => break,
+                _ => {}
+            }
+
+            let (i, mut candidates) = self
+                .pending
+                .pop_front()
+                .expect("Could not get pending thumbnail column");
+            let scores: Vec<&Score> = candidates
+                .iter()
+                .map(|candidate| &candidate.score)
+                .collect();
+            let best = best(&scores, cuts);
+            picked.push((i, candidates.swap_remove(best).thumbnail));
+        }
+
+        picked
+    }
+}
+
+// Measure how well the `thumbnail` of the frame at `pts` is suited as a thumbnail
+fn score(thumbnail: &frame::Frame, pts: f32) -> Score {
+    let luma = thumbnail.luma();
+
+    let count = luma.len().max(1) as f32;
+    let mean = luma.iter().sum::<f32>() / count;
+    let deviation = (luma.iter().map(|y| (y - mean).powi(2)).sum::<f32>() / count).sqrt();
+
+    Score {
+        pts,
+        blank: mean < MIN_BRIGHTNESS || deviation < MIN_CONTRAST,
+        sharpness: sharpness(&luma, thumbnail.width, thumbnail.height),
+    }
+}
+
+// Returns the variance of the Laplacian of a `width`*`height` image of `luma` values. Edges make
+// the Laplacian large, and blurry images have few of them.
+fn sharpness(luma: &[f32], width: usize, height: usize) -> f32 {
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let laplacian: Vec<f32> = (1..height - 1)
+        .flat_map(|y| (1..width - 1).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let i = y * width + x;
+            luma[i - width] + luma[i + width] + luma[i - 1] + luma[i + 1] - 4.0 * luma[i]
+        })
+        .collect();
+
+    let count = laplacian.len() as f32;
+    let mean = laplacian.iter().sum::<f32>() / count;
+    laplacian.iter().map(|l| (l - mean).powi(2)).sum::<f32>() / count
+}
+
+// Returns the index of the best of the `scores`. Blank frames are only picked if all frames are
+// blank, frames next to a cut only if all others are, and of the remaining frames, the sharpest
+// one wins.
+fn best(scores: &[&Score], cuts: &[cuts::Cut]) -> usize {
+    let near_cut = |score: &Score| {
+        cuts.iter()
+            .any(|cut| (cut.position - score.pts).abs() < CUT_MARGIN)
+    };
+
+    scores
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| {
+            (!a.blank)
+                .cmp(&!b.blank)
+                .then((!near_cut(a)).cmp(&!near_cut(b)))
+                .then(
+                    a.sharpness
+                        .partial_cmp(&b.sharpness)
+                        .unwrap_or(Ordering::Equal),
+                )
+        })
+        .map_or(0, |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cuts::Cut;
+    use crate::selection::{best, sharpness, Score};
+
+    #[test]
+    fn test_sharpness() {
+        let flat = vec![128.0; 64];
+        let checkerboard: Vec<f32> = (0..64)
+            .map(|i| if (i % 8 + i / 8) % 2 == 0 { 0.0 } else { 255.0 })
+            .collect();
+
+        assert_eq!(sharpness(&flat, 8, 8), 0.0);
+        assert!(sharpness(&checkerboard, 8, 8) > 1000.0);
+    }
+
+    #[test]
+    fn test_best() {
+        let score = |pts, blank, sharpness| Score {
+            pts,
+            blank,
+            sharpness,
+        };
+        let cuts = [Cut {
+            position: 1.0,
+            score: 1.0,
+        }];
+
+        // The sharpest frame is at the cut, and the next sharpest is black
+        let scores = [
+            score(0.6, false, 10.0),
+            score(0.8, true, 20.0),
+            score(1.0, false, 30.0),
+        ];
+        let scores: Vec<&Score> = scores.iter().collect();
+        assert_eq!(best(&scores, &cuts), 0);
+        assert_eq!(best(&scores, &[]), 2);
+    }
+}
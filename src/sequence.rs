@@ -0,0 +1,274 @@
+use std::fs;
+use std::path::Path;
+
+// Extensions of the supported image formats, and their media types
+const IMAGE_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("exr", "image/x-exr"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("bmp", "image/bmp"),
+    ("webp", "image/webp"),
+    ("gif", "image/gif"),
+    ("dpx", "image/x-dpx"),
+];
+
+// Numbered image files, which are decoded like the frames of a video
+#[derive(Debug, PartialEq)]
+pub struct Sequence {
+    // printf-style pattern of the filenames, like "frames/shot_%04d.png"
+    pub location: String,
+    // Extension of the files, in lowercase
+    pub extension: String,
+    // Number of the first image
+    pub first: u32,
+    // Number of the last image
+    pub last: u32,
+}
+
+impl Sequence {
+    // Number of images in the sequence
+    pub fn count(&self) -> usize {
+        (self.last - self.first + 1) as usize
+    }
+
+    // Media type of the images
+    pub fn media_type(&self) -> &'static str {
+        media_type(&self.extension).expect("Could not get media type of image sequence")
+    }
+}
+
+// Whether `input` is a glob pattern like "frames/*.png". URIs can contain "?", but aren't patterns.
+pub fn is_glob(input: &str) -> bool {
+    !crate::source::is_uri(input) && (input.contains('*') || input.contains('?'))
+}
+
+// If `input` is a directory or a glob pattern, find the image sequence it refers to. All files in
+// the directory, or all files matching the pattern, have to be numbered consecutively.
+pub fn find(input: &str) -> Result<Option<Sequence>, String> {
+    let path = Path::new(input);
+    let (directory, pattern) = if path.is_dir() {
+        (path, "*")
+    } else if is_glob(input) && !path.exists() {
+        let pattern = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        let directory = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        if is_glob(&directory.to_string_lossy()) {
+            return Err(String::from(
+                "Patterns for image sequences can only match filenames, not directories.",
+            ));
+        }
+        (directory, pattern)
+    } else {
+        return Ok(None);
+    };
+
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(format!(
+                "Could not read directory '{}': {}",
+                directory.display(),
+                e
+            ))
+        }
+    };
+
+    // Hidden files are ignored, unless the pattern asks for them
+    let mut names = vec![];
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.path().is_file()
+            && (!name.starts_with('.') || pattern.starts_with('.'))
+            && matches(pattern, &name)
+        {
+            names.push(name);
+        }
+    }
+
+    if names.is_empty() {
+        return Err(format!("No images were found for '{}'.", input));
+    }
+
+    let (location, first, last) = numbering(&names)?;
+    let extension = Path::new(&location)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if media_type(&extension).is_none() {
+        return Err(format!(
+            "The images of '{}' are not in a supported format. Supported are {}.",
+            input,
+            IMAGE_TYPES
+                .iter()
+                .map(|(extension, _)| *extension)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(Some(Sequence {
+        location: directory.join(location).to_string_lossy().into_owned(),
+        extension,
+        first,
+        last,
+    }))
+}
+
+// Returns the media type of images with the given file `extension`, if it is supported
+fn media_type(extension: &str) -> Option<&'static str> {
+    IMAGE_TYPES
+        .iter()
+        .find(|(image_extension, _)| *image_extension == extension)
+        .map(|(_, media_type)| *media_type)
+}
+
+// Whether `name` matches the glob `pattern`, where "*" matches any number of characters, and "?"
+// matches exactly one
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Position after the last "*", and the position in `name` it is currently assumed to match to
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last "*" match one more character
+            p = star_p;
+            n = star_n + 1;
+            star = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Find the printf-style pattern which generates all `names`, and the first and last number. The
+// number is the last group of digits in each name.
+fn numbering(names: &[String]) -> Result<(String, u32, u32), String> {
+    let mut prefix = None;
+    let mut suffix = None;
+    let mut widths = vec![];
+    let mut numbers = vec![];
+
+    for name in names {
+        let end = match name.rfind(|c: char| c.is_ascii_digit()) {
+            Some(end) => end + 1,
+            None => return Err(format!("The image '{}' is not numbered.", name)),
+        };
+        let start = name[..end]
+            .rfind(|c: char| !c.is_ascii_digit())
+            .map_or(0, |start| start + 1);
+
+        let (name_prefix, name_suffix) = (&name[..start], &name[end..]);
+        if *prefix.get_or_insert(name_prefix) != name_prefix
+            || *suffix.get_or_insert(name_suffix) != name_suffix
+        {
+            return Err(format!(
+                "The images '{}' and '{}' don't belong to the same sequence.",
+                names[0], name
+            ));
+        }
+
+        let number = match name[start..end].parse::<u32>() {
+            Ok(number) => number,
+            Err(_) => return Err(format!("The number of the image '{}' is too large.", name)),
+        };
+        widths.push(end - start);
+        numbers.push(number);
+    }
+
+    numbers.sort();
+    for pair in numbers.windows(2) {
+        if pair[1] != pair[0] + 1 {
+            return Err(format!(
+                "The image sequence is missing image number {}.",
+                pair[0] + 1
+            ));
+        }
+    }
+
+    // Zero-padded numbers all have the same width
+    let format = if widths.iter().all(|&width| width == widths[0]) && widths[0] > 1 {
+        format!("%0{}d", widths[0])
+    } else {
+        String::from("%d")
+    };
+
+    let escape = |part: &str| part.replace('%', "%%");
+    Ok((
+        format!(
+            "{}{}{}",
+            escape(prefix.unwrap_or("")),
+            format,
+            escape(suffix.unwrap_or(""))
+        ),
+        numbers[0],
+        numbers[numbers.len() - 1],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_matches() {
+        use crate::sequence::matches;
+        assert!(matches("*.png", "shot_0001.png"));
+        assert!(matches("shot_????.png", "shot_0001.png"));
+        assert!(matches("*_*.png", "shot_0001.png"));
+        assert!(!matches("*.png", "shot_0001.exr"));
+        assert!(!matches("shot_???.png", "shot_0001.png"));
+    }
+
+    #[test]
+    fn test_media_type() {
+        use crate::sequence::media_type;
+        assert_eq!(media_type("jpeg"), Some("image/jpeg"));
+        assert_eq!(media_type("txt"), None);
+    }
+
+    #[test]
+    fn test_numbering() {
+        use crate::sequence::numbering;
+
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|&name| String::from(name))
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            numbering(&names(&["v2_0010.exr", "v2_0009.exr", "v2_0011.exr"])),
+            Ok((String::from("v2_%04d.exr"), 9, 11))
+        );
+        assert_eq!(
+            numbering(&names(&["9.png", "10.png"])),
+            Ok((String::from("%d.png"), 9, 10))
+        );
+        assert_eq!(
+            numbering(&names(&["100%_1.png"])),
+            Ok((String::from("100%%_%d.png"), 1, 1))
+        );
+        assert!(numbering(&names(&["1.png", "3.png"])).is_err());
+        assert!(numbering(&names(&["a1.png", "b2.png"])).is_err());
+        assert!(numbering(&names(&["cover.png"])).is_err());
+    }
+}
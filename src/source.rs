@@ -1,12 +1,43 @@
 extern crate gstreamer as gst;
 extern crate gstreamer_app as gst_app;
 
+use crate::audio;
 use crate::frame;
+use crate::playlist;
+use crate::sequence;
 use crate::source::gst::prelude::*;
+use crate::spectrogram;
+use std::cmp;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-// A reference to exactly one of a file's video streams.
+// URI GStreamer uses to read from stdin
+const STDIN_URI: &str = "fd://0";
+
+// Methods of GStreamer's deinterlace element which can be selected
+pub const DEINTERLACE_METHODS: [&str; 7] = [
+    "linear",
+    "linearblend",
+    "scalerbob",
+    "vfir",
+    "greedyl",
+    "greedyh",
+    "tomsmocomp",
+];
+
+// Number of frames which are sampled to detect black borders
+const CROP_SAMPLES: usize = 8;
+
+// Rows and columns with an average luma below this value are considered to be black
+const BLACK_THRESHOLD: usize = 24;
+
+// Seconds of audio summarized in one spectrogram column, if the duration of the input is unknown
+const UNKNOWN_DURATION_INTERVAL: f32 = 0.1;
+
+// A reference to exactly one of a file's video streams. Several files can also be concatenated, and
+// are then treated as one continuous video.
 //
 // This type provides an `Iterator` interface which returns frames from the video in an
 // unspecified order.
@@ -15,42 +46,465 @@ pub struct VideoSource {
     pub height: usize,
     // Width of the output frames
     pub width: usize,
-    // Duration of the video in seconds
+    // Position in the video where the processed range starts, in seconds
+    pub start: f32,
+    // Duration of the processed range in seconds. If it isn't known in advance, this is updated
+    // while decoding, and only final once the source is exhausted.
     pub duration: f32,
+    // Whether `duration` was known before decoding
+    pub duration_known: bool,
+    // Position of the first frame in the input in seconds. All other positions, including `start`,
+    // are relative to it.
+    pub offset: f32,
+    // Information about the input file itself
+    pub meta: Meta,
+    // Borders which are removed from the input frames before scaling them
+    pub crop: Crop,
+    // Input files which are concatenated, and where they are in the combined video. This is empty
+    // if there is only a single input file.
+    pub parts: Vec<Part>,
+    // Level of the audio track over time, if it is analyzed. It is only complete once the source
+    // is exhausted.
+    pub audio: Option<Arc<Mutex<audio::Levels>>>,
+    // Why the source ended before the end of the input, if one of the later parts could not be
+    // opened
+    pub failure: Option<String>,
 
+    // Index of the part which is currently decoded
+    part: usize,
+    // Position of the first frame of the current part
+    part_offset: f32,
+    // Settings the following parts are opened with
+    options: Options,
+    // Turns the audio into frames, for inputs without video
+    spectrogram: Option<spectrogram::Spectrogram>,
+    // First frame of a live source, which had to be pulled before the others
+    first_sample: Option<gst::Sample>,
+    // First element of the branch which measures the audio track, besides the video
+    audio_branch: Option<gst::Element>,
     pipeline: gst::Pipeline,
     appsink: gst_app::AppSink,
     seek_mode: bool,
+    // Whether frames outside of the processed range arrive, because the input couldn't seek
+    skip_outside_range: bool,
     n: usize,
     next_column: usize,
 }
 
+// Size and duration of an input file, as reported by the selected video stream
+pub struct Meta {
+    // Width of the video in pixels
+    pub width: usize,
+    // Height of the video in pixels
+    pub height: usize,
+    // Display aspect ratio, which takes non-square pixels into account
+    pub aspect_ratio: f32,
+    // Duration of the video in seconds, if the input reports it. Live recordings, raw elementary
+    // streams and growing files often don't.
+    pub duration: Option<f32>,
+    // Frames per second, if the video has a constant framerate
+    pub framerate: Option<f32>,
+    // Index of the selected video stream
+    pub stream: usize,
+    // Identifier of the selected video stream, if the container provides one
+    pub stream_id: Option<String>,
+    // Whether the input supports seeking. This is not the case for live streams, or for HTTP
+    // servers which don't support range requests.
+    pub seekable: bool,
+    // Whether the input has no video stream, but audio. Its frames then show a spectrogram, and
+    // the size is zero.
+    pub audio_only: bool,
+}
+
+// Description of one of the video streams of an input file
+pub struct Stream {
+    // Position among the file's video streams, starting at 0
+    pub index: usize,
+    // Unique identifier of the stream, if the container provides one
+    pub id: Option<String>,
+    // Language code of the stream, if it is tagged with one
+    pub language: Option<String>,
+    // Clockwise rotation in degrees which is needed to display the video upright
+    pub rotation: u32,
+    // Width of the video in pixels, after rotating it
+    pub width: usize,
+    // Height of the video in pixels, after rotating it
+    pub height: usize,
+    // Display aspect ratio, which takes non-square pixels and the rotation into account
+    pub aspect_ratio: f32,
+    // Frames per second, if the video has a constant framerate
+    pub framerate: Option<f32>,
+}
+
+// Number of pixels to remove from each side of the input frames, for example to get rid of
+// letterboxing or pillarboxing
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Crop {
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+    pub left: usize,
+}
+
+// How interlaced input frames are converted to progressive ones
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Deinterlace {
+    // Whether to deinterlace all frames, or none of them. If this is `None`, only frames which
+    // are marked as interlaced are deinterlaced.
+    pub enabled: Option<bool>,
+    // One of `DEINTERLACE_METHODS`, or `None` for GStreamer's default
+    pub method: Option<String>,
+}
+
+// Where a pipeline reads the video from
+enum Input {
+    // Anything uridecodebin can open
+    Uri(String),
+    // Numbered images, which are shown at the given framerate
+    Sequence(sequence::Sequence, gst::Fraction),
+}
+
+// How a pipeline recognizes the pad of the selected video stream on the decoder
+#[derive(Clone)]
+enum StreamPad {
+    // The pad with this stream identifier
+    Id(String),
+    // The n-th video pad, for inputs whose streams have no identifiers
+    Index(usize),
+}
+
+impl StreamPad {
+    fn from_meta(meta: &Meta) -> StreamPad {
+        match meta.stream_id {
+            Some(ref id) => StreamPad::Id(id.clone()),
+            None => StreamPad::Index(meta.stream),
+        }
+    }
+}
+
+// One of several input files which are concatenated
+pub struct Part {
+    // Name or URI of the file
+    pub filename: String,
+    // Position in the combined video where this file starts, in seconds
+    pub start: f32,
+    // Position in the file where the used range starts, in seconds
+    pub inpoint: f32,
+    // Duration of the used range in seconds, which is the whole file unless an EDL selects less
+    pub duration: f32,
+}
+
+// Settings which influence how a `VideoSource` produces its frames
+#[derive(Clone)]
+pub struct Options {
+    // Borders to remove from the input frames. If this is `None`, black borders are detected
+    // automatically, which seeks to several frames, so it is only done on request.
+    pub crop: Option<Crop>,
+    // Index, identifier or language code of the video stream to use. If this is `None`, the first
+    // video stream is used.
+    pub stream: Option<String>,
+    // Position in seconds where processing starts
+    pub start: f32,
+    // Position in seconds where processing ends. If this is `None`, the whole rest of the video is
+    // processed.
+    pub end: Option<f32>,
+    // Don't trust the duration the input reports, and decode until the end instead
+    pub ignore_duration: bool,
+    // How to deinterlace the input frames
+    pub deinterlace: Deinterlace,
+    // Framerate of image sequences, as numerator and denominator
+    pub framerate: (u32, u32),
+    // Whether to measure the level of the audio track
+    pub audio: bool,
+    // Output at least this many frames per second, for analyses which need to look at the video
+    // more closely than the timeline does
+    pub min_framerate: Option<f32>,
+}
+
 impl VideoSource {
-    // Initializes a new `VideoSource`, referencing the specified video `filename`.
+    // Initializes a new `VideoSource`, referencing the specified video `filenames`, which can also
+    // be URIs, or "-" to read from stdin. Several files are played one after another, using the
+    // range of each file its entry in `edits` selects, if any.
     //
     // Any frames this source outputs will be `output_height` pixels high. The source will try to
     // output approximately `n` frames.
-    pub fn new(filename: &str, output_height: usize, n: usize) -> Result<Self, String> {
+    pub fn new(
+        filenames: &[String],
+        edits: &[Option<playlist::Edit>],
+        output_height: usize,
+        n: usize,
+        options: &Options,
+    ) -> Result<Self, String> {
         // Initialize GStreamer
         gst::init().expect("Could not initialize GStreamer");
 
-        // Get size and duration information
-        let (aspect_ratio, duration) = get_meta(&filename)?;
+        let levels = if options.audio {
+            Some(Arc::new(Mutex::new(audio::Levels::default())))
+        } else {
+            None
+        };
+
+        if filenames.len() == 1 && edits.iter().all(Option::is_none) {
+            return Self::open(&filenames[0], output_height, None, n, options, &levels, 0.0);
+        }
+
+        if options.start > 0.0 || options.end.is_some() {
+            return Err(String::from(
+                "A range can't be selected when combining several input files.",
+            ));
+        }
+        if options.ignore_duration || filenames.iter().any(|filename| filename == "-") {
+            return Err(String::from(
+                "Combining several input files requires knowing their durations.",
+            ));
+        }
+
+        // Lay out the parts one after another
+        let mut parts = vec![];
+        let mut total_duration = 0.0;
+        let mut audio_only = None;
+        for (filename, edit) in filenames.iter().zip(edits) {
+            let meta = get_meta(&input_uri(&filename)?, &options.stream)?;
+            if *audio_only.get_or_insert(meta.audio_only) != meta.audio_only {
+                return Err(String::from(
+                    "Files without video can't be combined with video files.",
+                ));
+            }
+            let duration = match meta.duration {
+                Some(duration) => duration,
+                None => {
+                    return Err(format!(
+                        "The duration of '{}' is unknown, so it can't be combined with other \
+                         files.",
+                        filename
+                    ))
+                }
+            };
+
+            // Timecodes count frames, so files without video count them at the framerate of image
+            // sequences
+            let (inpoint, duration) = match edit {
+                Some(edit) => {
+                    let framerate = meta
+                        .framerate
+                        .unwrap_or(options.framerate.0 as f32 / options.framerate.1 as f32);
+                    let inpoint = edit.from.to_seconds(framerate);
+                    let outpoint = edit.to.to_seconds(framerate).min(duration);
+                    if inpoint >= outpoint {
+                        return Err(format!(
+                            "The range of '{}' in the EDL is outside of the file.",
+                            filename
+                        ));
+                    }
+                    (inpoint, outpoint - inpoint)
+                }
+                None => (0.0, duration),
+            };
+
+            parts.push(Part {
+                filename: filename.clone(),
+                start: total_duration,
+                inpoint,
+                duration,
+            });
+            total_duration += duration;
+        }
+
+        // The other parts are only opened once the first one is done
+        let mut source = Self::open(
+            &parts[0].filename,
+            output_height,
+            None,
+            part_frames(n, parts[0].duration, total_duration),
+            &part_options(options, &parts[0]),
+            &levels,
+            -parts[0].inpoint,
+        )?;
+        source.start = 0.0;
+        source.duration = total_duration;
+        source.meta.duration = Some(total_duration);
+        source.n = n;
+        source.parts = parts;
+        // Spectrogram columns span the combined audio
+        if let Some(ref mut spectrogram) = source.spectrogram {
+            *spectrogram =
+                spectrogram::Spectrogram::new(total_duration / n as f32, 0.0, output_height);
+        }
+        Ok(source)
+    }
+
+    // Open a single input file, which starts at `position` in the combined video. If
+    // `output_width` is `None`, it is chosen so that the aspect ratio is kept.
+    fn open(
+        filename: &str,
+        output_height: usize,
+        output_width: Option<usize>,
+        n: usize,
+        options: &Options,
+        levels: &Option<Arc<Mutex<audio::Levels>>>,
+        position: f32,
+    ) -> Result<Self, String> {
+        let input = match sequence::find(&filename)? {
+            Some(sequence) => Input::Sequence(
+                sequence,
+                gst::Fraction::new(options.framerate.0 as i32, options.framerate.1 as i32),
+            ),
+            None => Input::Uri(input_uri(&filename)?),
+        };
+
+        // Get size and duration information. Stdin can only be read once, and image sequences
+        // can't be probed, so in these cases, we have to get them from the decoding pipeline
+        // itself.
+        let (meta, crop) = match input {
+            Input::Uri(ref uri) if uri != STDIN_URI => {
+                let meta = get_meta(&uri, &options.stream)?;
+                if meta.audio_only {
+                    return Self::open_audio(
+                        uri,
+                        meta,
+                        output_height,
+                        n,
+                        options,
+                        levels,
+                        position,
+                    );
+                }
+
+                // Find out which borders to remove, looking only at the frames which are processed
+                let crop = match (options.crop, meta.duration) {
+                    (Some(crop), _) => crop,
+                    (None, Some(duration)) if meta.seekable => {
+                        let end = options.end.map_or(duration, |end| end.min(duration));
+                        detect_crop(&uri, &meta, options.start.min(end), end)
+                    }
+                    // Detection needs to seek, so we can't do it for other inputs
+                    (None, _) => {
+                        return Err(String::from(
+                            "Borders can only be detected in inputs which can seek, and whose \
+                             duration is known.",
+                        ))
+                    }
+                };
+
+                check_crop(&crop, &meta)?;
+                (Some(meta), crop)
+            }
+            _ => {
+                if options.stream.is_some() {
+                    return Err(String::from(
+                        "Selecting a stream is not supported for stdin and image sequences.",
+                    ));
+                }
+                if options.crop.is_none() {
+                    return Err(String::from(
+                        "Detecting borders is not supported for stdin and image sequences.",
+                    ));
+                }
+                (None, options.crop.unwrap_or_default())
+            }
+        };
 
-        // Calculate which output width keeps the aspect ratio
-        let output_width = (output_height as f32 * aspect_ratio) as usize;
+        // Calculate which output width keeps the aspect ratio of the cropped frames. If we don't
+        // know the aspect ratio yet, the pipeline will choose the width.
+        let output_width = output_width.or_else(|| {
+            meta.as_ref().map(|meta| {
+                let aspect_ratio = meta.aspect_ratio
+                    * (meta.width - crop.left - crop.right) as f32
+                    * meta.height as f32
+                    / meta.width as f32
+                    / (meta.height - crop.top - crop.bottom) as f32;
+                (output_height as f32 * aspect_ratio) as usize
+            })
+        });
 
         // Set up GStreamer pipeline
-        let (pipeline, capsfilter, appsink) =
-            build_pipeline(&filename, output_width, output_height);
+        let stream_pad = meta
+            .as_ref()
+            .map_or(StreamPad::Index(0), StreamPad::from_meta);
+        let (pipeline, capsfilter, appsink, audio_branch) = build_pipeline(
+            &input,
+            output_width,
+            output_height,
+            &crop,
+            &stream_pad,
+            &options.deinterlace,
+            levels,
+        );
 
-        // Set the input pipeline to paused to fill the buffers
-        pipeline
-            .set_state(gst::State::Paused)
-            .into_result()
-            .expect("Could not pause input pipeline");
+        // Set the input pipeline to paused to fill the buffers. Live sources like cameras or RTSP
+        // streams don't produce any data while paused, so they can't preroll.
+        let live = match pipeline.set_state(gst::State::Paused).into_result() {
+            Ok(gst::StateChangeSuccess::NoPreroll) => true,
+            Ok(_) => false,
+            Err(_) => return Err(String::from("Input file could not be opened")),
+        };
         pipeline.get_state(10 * gst::SECOND);
 
+        let meta = match meta {
+            Some(meta) => meta,
+            None => {
+                let mut meta = get_pipeline_meta(&pipeline)?;
+                // The length of an image sequence follows from its framerate
+                if let Input::Sequence(ref sequence, ref framerate) = input {
+                    meta.duration = Some(
+                        sequence.count() as f32 * *framerate.denom() as f32
+                            / *framerate.numer() as f32,
+                    );
+                }
+                check_crop(&crop, &meta)?;
+                meta
+            }
+        };
+
+        // Live sources only produce their first frame when playing. It is kept, so that it is
+        // still the first one the source outputs.
+        let first_sample = if live {
+            pipeline
+                .set_state(gst::State::Playing)
+                .into_result()
+                .expect("Could not start input pipeline");
+            appsink.pull_sample()
+        } else {
+            appsink.pull_preroll()
+        };
+        let first_sample = match first_sample {
+            Some(sample) => sample,
+            None => return Err(String::from("The input does not contain any frames.")),
+        };
+
+        // Streams don't necessarily start at zero, for example MPEG-TS captures or trimmed files,
+        // so we map all positions relative to the first frame
+        let offset = stream_time(&first_sample);
+        let lead = offset - container_start(&first_sample);
+
+        // Audio samples are positioned like the frames
+        if let Some(levels) = levels {
+            levels
+                .lock()
+                .expect("Could not lock audio levels")
+                .begin(position, offset);
+        }
+
+        // Now that the first frame has arrived, we know which width the pipeline chose
+        let output_width = match output_width {
+            Some(output_width) => output_width,
+            None => first_sample
+                .get_caps()
+                .expect("Could not get caps of first frame")
+                .get_structure(0)
+                .expect("Could not get structure from caps of first frame")
+                .get_value("width")
+                .expect("Could not get width from caps of first frame")
+                .get::<i32>()
+                .expect("Could not convert width of first frame to i32")
+                as usize,
+        };
+
+        let (start, end, total_duration) = processed_range(options, meta.duration, lead)?;
+        let duration = end.map(|end| end - start);
+        let skip_outside_range =
+            seek_to_range(&pipeline, meta.seekable, offset, start, end, total_duration);
+
         let seek_mode = false;
 
         // If we don't seek, start playing
@@ -61,14 +515,17 @@ impl VideoSource {
                 .expect("Could not start input pipeline");
         }
 
-        // Approximate which FPS value is required to output n frames in total
-        let fps = gst::Fraction::new((n * 100) as i32, (duration * 100.0) as i32);
-
         // Set the capsfilter element correctly so that the pipeline will output the correct format
-        capsfilter
-            .set_property(
-                "caps",
-                &gst::Caps::new_simple(
+        let caps = match duration {
+            Some(duration) => {
+                // Approximate which FPS value is required to output n frames in total
+                let fps = match options.min_framerate {
+                    Some(min_framerate) if min_framerate * duration > n as f32 => {
+                        gst::Fraction::new((min_framerate * 100.0) as i32, 100)
+                    }
+                    _ => gst::Fraction::new((n * 100) as i32, (duration * 100.0) as i32),
+                };
+                gst::Caps::new_simple(
                     "video/x-raw",
                     &[
                         ("format", &"BGRx"),
@@ -76,22 +533,253 @@ impl VideoSource {
                         ("width", &(output_width as i32)),
                         ("height", &(output_height as i32)),
                     ],
-                ),
-            )
+                )
+            }
+            // Without a duration, we can't know how many frames we need, so we get all of them
+            None => gst::Caps::new_simple(
+                "video/x-raw",
+                &[
+                    ("format", &"BGRx"),
+                    ("width", &(output_width as i32)),
+                    ("height", &(output_height as i32)),
+                ],
+            ),
+        };
+        capsfilter
+            .set_property("caps", &caps)
             .expect("Could not set properties on input capsfilter");
 
         // Return the new VideoSource
         Ok(Self {
             width: output_width,
             height: output_height,
-            duration,
+            start,
+            duration: duration.unwrap_or(0.0),
+            duration_known: duration.is_some(),
+            offset,
+            meta,
+            crop,
+            parts: vec![],
+            audio: levels.clone(),
+            failure: None,
+            part: 0,
+            part_offset: offset,
+            options: options.clone(),
+            spectrogram: None,
+            first_sample: if live { Some(first_sample) } else { None },
+            audio_branch,
+            pipeline,
+            seek_mode: false,
+            skip_outside_range,
+            appsink,
+            n,
+            next_column: 0,
+        })
+    }
+
+    // Open an input file which only contains audio. Its frames are the columns of a spectrogram,
+    // which are 1 pixel wide.
+    fn open_audio(
+        uri: &str,
+        meta: Meta,
+        output_height: usize,
+        n: usize,
+        options: &Options,
+        levels: &Option<Arc<Mutex<audio::Levels>>>,
+        position: f32,
+    ) -> Result<Self, String> {
+        let (pipeline, appsink) = build_audio_pipeline(uri);
+
+        if pipeline
+            .set_state(gst::State::Paused)
+            .into_result()
+            .is_err()
+        {
+            return Err(String::from("Input file could not be opened"));
+        }
+        pipeline.get_state(10 * gst::SECOND);
+
+        let first_sample = match appsink.pull_preroll() {
+            Some(sample) => sample,
+            None => return Err(String::from("The input does not contain any audio.")),
+        };
+        let offset = stream_time(&first_sample);
+        let lead = offset - container_start(&first_sample);
+        if let Some(levels) = levels {
+            levels
+                .lock()
+                .expect("Could not lock audio levels")
+                .begin(position, offset);
+        }
+
+        let (start, end, total_duration) = processed_range(options, meta.duration, lead)?;
+        let duration = end.map(|end| end - start);
+        let skip_outside_range =
+            seek_to_range(&pipeline, meta.seekable, offset, start, end, total_duration);
+
+        pipeline
+            .set_state(gst::State::Playing)
+            .into_result()
+            .expect("Could not start input pipeline");
+
+        // Without a duration, the columns summarize short intervals, which are combined later
+        let interval = duration.map_or(UNKNOWN_DURATION_INTERVAL, |duration| duration / n as f32);
+
+        Ok(Self {
+            width: 1,
+            height: output_height,
+            start,
+            duration: duration.unwrap_or(0.0),
+            duration_known: duration.is_some(),
+            offset,
+            meta,
+            crop: Crop::default(),
+            parts: vec![],
+            audio: levels.clone(),
+            failure: None,
+            part: 0,
+            part_offset: offset,
+            options: options.clone(),
+            spectrogram: Some(spectrogram::Spectrogram::new(
+                interval,
+                start,
+                output_height,
+            )),
+            first_sample: None,
+            audio_branch: None,
             pipeline,
             seek_mode: false,
+            skip_outside_range,
             appsink,
             n,
             next_column: 0,
         })
     }
+
+    // Switch to the next part, if there is one. Returns whether there was.
+    fn next_part(&mut self) -> Result<bool, String> {
+        if self.part + 1 >= self.parts.len() {
+            return Ok(false);
+        }
+
+        self.finish_audio();
+        self.pipeline
+            .set_state(gst::State::Null)
+            .into_result()
+            .expect("Could not stop input pipeline");
+
+        self.part += 1;
+        let part = &self.parts[self.part];
+
+        // All parts are scaled to the same size, even if their aspect ratios differ
+        let source = Self::open(
+            &part.filename,
+            self.height,
+            Some(self.width),
+            part_frames(self.n, part.duration, self.duration),
+            &part_options(&self.options, part),
+            &self.audio,
+            part.start - part.inpoint,
+        )
+        .map_err(|message| format!("Could not open '{}': {}", part.filename, message))?;
+
+        self.part_offset = source.offset;
+        self.skip_outside_range = source.skip_outside_range;
+        self.first_sample = source.first_sample;
+        self.audio_branch = source.audio_branch;
+        self.pipeline = source.pipeline;
+        self.appsink = source.appsink;
+        Ok(true)
+    }
+
+    // Get the next frame from the pipeline, and its position in the combined video. For inputs
+    // without video, this is the next column of the spectrogram.
+    fn pull(&mut self) -> Option<(f32, gst::Buffer)> {
+        // Positions in the current part are relative to the start of its range
+        let part_start = self
+            .parts
+            .get(self.part)
+            .map_or(0.0, |part| part.start - part.inpoint);
+
+        let spectrogram = match self.spectrogram {
+            Some(ref mut spectrogram) => spectrogram,
+            None => loop {
+                let sample = match self.first_sample.take() {
+                    Some(sample) => sample,
+                    None => self.appsink.pull_sample()?,
+                };
+                let pts = stream_time(&sample) - self.part_offset + part_start;
+
+                // Parts which couldn't seek to their range are decoded completely, so the part
+                // ends with the first frame after its range
+                if let (true, Some(part)) = (self.skip_outside_range, self.parts.get(self.part)) {
+                    if pts < part.start {
+                        continue;
+                    }
+                    if pts > part.start + part.duration {
+                        return None;
+                    }
+                }
+
+                return Some((
+                    pts,
+                    sample
+                        .get_buffer()
+                        .expect("Could not get buffer from input pipeline"),
+                ));
+            },
+        };
+
+        loop {
+            if let Some((pts, column)) = spectrogram.next_column() {
+                return Some((pts, column.buffer));
+            }
+
+            let sample = match self.appsink.pull_sample() {
+                Some(sample) => sample,
+                // Columns can span several parts, so only the last part finishes them
+                None if self.part + 1 >= self.parts.len() => {
+                    return spectrogram
+                        .finish()
+                        .map(|(pts, column)| (pts, column.buffer));
+                }
+                None => return None,
+            };
+
+            let time = stream_time(&sample);
+            let (rate, samples) = audio_samples(&sample);
+            spectrogram.add(time - self.part_offset + part_start, rate, &samples);
+            if let Some(ref levels) = self.audio {
+                levels
+                    .lock()
+                    .expect("Could not lock audio levels")
+                    .add(time, rate, &samples);
+            }
+        }
+    }
+
+    // Wait until the pipeline reached its end, because audio samples can still arrive after the
+    // last frame. If the input has no audio stream, nothing was linked to the audio branch, and
+    // there is nothing to wait for.
+    fn finish_audio(&self) {
+        let linked = self.audio_branch.as_ref().map_or(false, |audio_branch| {
+            audio_branch
+                .get_static_pad("sink")
+                .expect("Failed to get static sink pad from audioconvert")
+                .is_linked()
+        });
+        if !linked {
+            return;
+        }
+
+        self.pipeline
+            .get_bus()
+            .expect("Could not get bus of input pipeline")
+            .timed_pop_filtered(
+                10 * gst::SECOND,
+                &[gst::MessageType::Eos, gst::MessageType::Error],
+            );
+    }
 }
 
 impl Iterator for VideoSource {
@@ -99,7 +787,10 @@ impl Iterator for VideoSource {
 
     fn next(&mut self) -> Option<frame::Frame> {
         if self.seek_mode {
-            let j = (self.duration * 1_000_000_000.0) / self.n as f32 * self.next_column as f32;
+            let j = (self.offset
+                + self.start
+                + self.duration / self.n as f32 * self.next_column as f32)
+                * 1_000_000_000.0;
 
             self.pipeline
                 .seek_simple(
@@ -109,54 +800,182 @@ impl Iterator for VideoSource {
                 .expect("Could not seek");
         }
 
-        match self.appsink.pull_sample() {
-            Some(sample) => {
-                self.next_column += 1;
-                Some(frame::Frame {
-                    buffer: sample
-                        .get_buffer()
-                        .expect("Could not get buffer from input pipeline"),
-                    width: self.width,
-                    height: self.height,
-                    pts: Some(
-                        sample
-                            .get_buffer()
-                            .expect("Could not get buffer from input pipeline, again")
-                            .get_pts()
-                            .nseconds()
-                            .expect("Could not convert PTS to nanoseconds in input pipeline")
-                            as f32
-                            / 1_000_000_000.0,
-                    ),
-                })
+        loop {
+            let (pts, buffer) = match self.pull() {
+                Some(frame) => frame,
+                None => match self.next_part() {
+                    Ok(true) => continue,
+                    Ok(false) => {
+                        self.finish_audio();
+                        break;
+                    }
+                    Err(message) => {
+                        self.failure = Some(message);
+                        break;
+                    }
+                },
+            };
+
+            if self.skip_outside_range {
+                if pts < self.start {
+                    continue;
+                }
+                if self.duration_known && pts > self.start + self.duration {
+                    break;
+                }
             }
-            None => {
-                // We are at the end of the video. Stop pipeline and return None.
-                self.pipeline
-                    .set_state(gst::State::Null)
-                    .into_result()
-                    .expect("Could not stop input pipeline");
 
-                None
+            if !self.duration_known && pts - self.start > self.duration {
+                self.duration = pts - self.start;
             }
+
+            self.next_column += 1;
+            return Some(frame::Frame {
+                buffer,
+                width: self.width,
+                height: self.height,
+                pts: Some(pts),
+            });
+        }
+
+        // We are at the end of the video. Stop pipeline and return None.
+        self.pipeline
+            .set_state(gst::State::Null)
+            .into_result()
+            .expect("Could not stop input pipeline");
+
+        None
+    }
+}
+
+// Settings to open a `part` of the combined video with, which only process its range
+fn part_options(options: &Options, part: &Part) -> Options {
+    Options {
+        start: part.inpoint,
+        end: Some(part.inpoint + part.duration),
+        ..options.clone()
+    }
+}
+
+// Number of frames out of `n` a part of the combined video should output, so that they are spread
+// evenly
+fn part_frames(n: usize, duration: f32, total_duration: f32) -> usize {
+    cmp::max((n as f32 * duration / total_duration).round() as usize, 1)
+}
+
+// Find out which range of the input to process, given the `duration` it reports, and how many
+// seconds its first frame comes after the start of the container. Returns start and end of the
+// range, and the duration of the input, which are `None` if they are unknown.
+fn processed_range(
+    options: &Options,
+    duration: Option<f32>,
+    lead: f32,
+) -> Result<(f32, Option<f32>, Option<f32>), String> {
+    // The reported duration is measured from the start of the container, which isn't necessarily
+    // where the first frame is
+    let total_duration = match duration {
+        _ if options.ignore_duration => None,
+        Some(duration) => Some(duration - lead.max(0.0)),
+        None => None,
+    };
+    if let Some(total_duration) = total_duration {
+        if options.start >= total_duration {
+            return Err(format!(
+                "The start position must be before the end of the input, which is {} seconds \
+                 long.",
+                total_duration
+            ));
+        }
+    }
+    let start = options.start;
+    let end = match (options.end, total_duration) {
+        (Some(end), Some(total_duration)) => Some(end.min(total_duration)),
+        (end, None) => end,
+        (None, total_duration) => total_duration,
+    };
+
+    Ok((start, end, total_duration))
+}
+
+// Only decode the range from `start` to `end` of a paused `pipeline`. If the input can't seek, or
+// if we don't know where the range ends, everything is decoded, and whatever is outside of the
+// range has to be skipped later. Returns whether that's necessary.
+fn seek_to_range(
+    pipeline: &gst::Pipeline,
+    seekable: bool,
+    offset: f32,
+    start: f32,
+    end: Option<f32>,
+    total_duration: Option<f32>,
+) -> bool {
+    let partial = start > 0.0 || end != total_duration;
+    let skip_outside_range = partial && (!seekable || end.is_none());
+    if let (true, Some(end)) = (partial && !skip_outside_range, end) {
+        pipeline
+            .seek(
+                1.0,
+                gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                gst::SeekType::Set,
+                ((offset + start) * 1_000_000_000.0) as u64 * gst::NSECOND,
+                gst::SeekType::Set,
+                ((offset + end) * 1_000_000_000.0) as u64 * gst::NSECOND,
+            )
+            .expect("Could not seek to the requested range");
+        pipeline.get_state(10 * gst::SECOND);
+    }
+
+    skip_outside_range
+}
+
+// List the video streams of the input file
+pub fn list_streams(filename: &str) -> Result<Vec<Stream>, String> {
+    // Initialize GStreamer
+    gst::init().expect("Could not initialize GStreamer");
+
+    let (streams, _, _, _) = probe(&input_uri(&filename)?)?;
+    Ok(streams)
+}
+
+// Whether `input` is a URI like "https://example.com/video.mp4", instead of a filename
+pub fn is_uri(input: &str) -> bool {
+    match input.find("://") {
+        Some(0) | None => false,
+        Some(end) => {
+            let scheme = &input[..end];
+            scheme
+                .chars()
+                .next()
+                .map_or(false, |c| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
         }
     }
 }
 
-// Get resolution and duration of the input file
-fn get_meta(filename: &str) -> Result<(f32, f32), String> {
+// Returns the URI GStreamer should open for `input`. URIs are passed on unchanged, filenames are
+// checked and converted to file:// URIs.
+fn input_uri(input: &str) -> Result<String, String> {
+    if is_uri(&input) {
+        return Ok(String::from(input));
+    }
+
+    if input == "-" {
+        return Ok(String::from(STDIN_URI));
+    }
+
     // Generate file:// URI from an absolute filename
-    let path = PathBuf::from(filename);
+    let path = PathBuf::from(input);
 
     if path.is_dir() {
         return Err(format!(
             "Input argument '{}' is a directory. Please specify a file.",
-            &filename
+            &input
         ));
     }
 
     if !path.is_file() {
-        return Err(format!("Input file '{}' could not be found.", &filename));
+        return Err(format!("Input file '{}' could not be found.", &input));
     }
 
     let absolute = match fs::canonicalize(&path) {
@@ -168,8 +987,140 @@ fn get_meta(filename: &str) -> Result<(f32, f32), String> {
     let absolute = absolute
         .to_str()
         .expect("Could not convert absolut path to str");
-    let uri = format!("file://{}", absolute);
+    Ok(format!("file://{}", absolute))
+}
+
+// Get resolution and duration of the selected video stream of the input `uri`
+fn get_meta(uri: &str, selector: &Option<String>) -> Result<Meta, String> {
+    let (streams, audio_streams, duration, seekable) = probe(&uri)?;
+
+    if streams.is_empty() {
+        if audio_streams == 0 {
+            return Err(String::from("This does not seem to be a video file."));
+        }
+        if selector.is_some() {
+            return Err(String::from(
+                "Selecting a stream is not supported for inputs without video.",
+            ));
+        }
+
+        return Ok(Meta {
+            width: 0,
+            height: 0,
+            aspect_ratio: 0.0,
+            duration,
+            framerate: None,
+            stream: 0,
+            stream_id: None,
+            seekable,
+            audio_only: true,
+        });
+    }
+
+    let stream = match selector {
+        Some(selector) => select_stream(&streams, &selector)?,
+        None => &streams[0],
+    };
+
+    Ok(Meta {
+        width: stream.width,
+        height: stream.height,
+        aspect_ratio: stream.aspect_ratio,
+        duration,
+        framerate: stream.framerate,
+        stream: stream.index,
+        stream_id: stream.id.clone(),
+        seekable,
+        audio_only: false,
+    })
+}
+
+// Find the stream `selector` refers to. It can be the stream's index, its identifier, or its
+// language code, in that order of precedence. Streams whose pad could not be probed are missing
+// from `streams`, so they are looked up by their index rather than by their position.
+fn select_stream<'a>(streams: &'a [Stream], selector: &str) -> Result<&'a Stream, String> {
+    if let Ok(index) = selector.parse::<usize>() {
+        return match streams.iter().find(|stream| stream.index == index) {
+            Some(stream) => Ok(stream),
+            None => Err(format!(
+                "There is no video stream with index {}, the input file has {}.",
+                index,
+                streams.len()
+            )),
+        };
+    }
+
+    if let Some(stream) = streams
+        .iter()
+        .find(|stream| stream.id.as_ref().map(String::as_str) == Some(selector))
+    {
+        return Ok(stream);
+    }
+
+    if let Some(stream) = streams
+        .iter()
+        .find(|stream| stream.language.as_ref().map(String::as_str) == Some(selector))
+    {
+        return Ok(stream);
+    }
+
+    Err(format!(
+        "There is no video stream with the identifier or language '{}'.",
+        selector
+    ))
+}
+
+// Get width, height and display aspect ratio from the caps of a video stream
+fn get_geometry(caps: &gst::Caps) -> (usize, usize, f32) {
+    let width = caps
+        .get_structure(0)
+        .expect("Could not get structure from caps width")
+        .get_value("width")
+        .expect("Could not get width from caps")
+        .get::<i32>()
+        .expect("Could not convert width to i32") as usize;
+    let height = caps
+        .get_structure(0)
+        .expect("Could not get structure from caps height")
+        .get_value("height")
+        .expect("Could not get height from caps")
+        .get::<i32>()
+        .expect("Could not convert height to i32") as usize;
+
+    // Pixels aren't necessarily square, so we need to get their aspect ratio to calculate the
+    // aspect ratio of the video
+    let pixel_aspect_ratio = caps
+        .get_structure(0)
+        .expect("Could not get structure from caps aspect ratio")
+        .get_value("pixel-aspect-ratio")
+        .expect("Could not get aspect ratio from caps")
+        .get::<gst::Fraction>()
+        .expect("Could not convert aspect ratio to fraction");
+    let aspect_ratio = width as f32 * *pixel_aspect_ratio.numer() as f32
+        / height as f32
+        / *pixel_aspect_ratio.denom() as f32;
+
+    (width, height, aspect_ratio)
+}
+
+// Get the framerate from the caps of a video stream. Returns `None` if it is variable, which caps
+// describe as a framerate of zero.
+fn get_framerate(caps: &gst::Caps) -> Option<f32> {
+    let framerate = caps
+        .get_structure(0)?
+        .get_value("framerate")?
+        .get::<gst::Fraction>()?;
+
+    if *framerate.numer() > 0 && *framerate.denom() > 0 {
+        Some(*framerate.numer() as f32 / *framerate.denom() as f32)
+    } else {
+        None
+    }
+}
 
+// Get resolution, identifier and language of all video streams of the input `uri`, the number of
+// its audio streams, its duration, and whether it is seekable
+fn probe(uri: &str) -> Result<(Vec<Stream>, usize, Option<f32>, bool), String> {
     // Set up a playbin element, which automatically select decoders
     let playbin = gst::ElementFactory::make("playbin", None).expect("Could not create playbin");
     playbin
@@ -202,55 +1153,87 @@ fn get_meta(filename: &str) -> Result<(f32, f32), String> {
     }
     pipeline.get_state(10 * gst::SECOND);
 
-    // Get the sinkpad of the first video stream
-    let pad = playbin
-        .emit("get-video-pad", &[&0])
-        .expect("Could not get video pad")
-        .expect("Could not get video pad, part 2");
+    // Look at the sinkpad of every video stream
+    let stream_count = playbin
+        .get_property("n-video")
+        .expect("Could not get number of video streams")
+        .get::<i32>()
+        .expect("Could not convert number of video streams to i32") as usize;
 
-    let pad = if let Some(pad) = pad.get::<gst::Pad>() {
-        pad
-    } else {
-        return Err(String::from("This does not seem to be a video file."));
-    };
+    let mut streams = vec![];
 
-    // And retrieve width and height from its caps
-    let caps = pad.get_current_caps().expect("Could not get current caps");
-    let width = caps
-        .get_structure(0)
-        .expect("Could not get structure from caps width")
-        .get_value("width")
-        .expect("Could not get width from caps")
-        .get::<i32>()
-        .expect("Could not convert width to i32") as usize;
-    let height = caps
-        .get_structure(0)
-        .expect("Could not get structure from caps height")
-        .get_value("height")
-        .expect("Could not get height from caps")
-        .get::<i32>()
-        .expect("Could not convert height to i32") as usize;
+    for index in 0..stream_count {
+        let pad = playbin
+            .emit("get-video-pad", &[&(index as i32)])
+            .expect("Could not get video pad")
+            .expect("Could not get video pad, part 2");
 
-    // Pixels aren't necessarily square, so we need to get their aspect ratio to calculate the
-    // aspect ratio of the video
-    let pixel_aspect_ratio = caps
-        .get_structure(0)
-        .expect("Could not get structure from caps aspect ratio")
-        .get_value("pixel-aspect-ratio")
-        .expect("Could not get aspect ratio from caps")
-        .get::<gst::Fraction>()
-        .expect("Could not convert aspect ratio to fraction");
-    let aspect_ratio = width as f32 * *pixel_aspect_ratio.numer() as f32
-        / height as f32
-        / *pixel_aspect_ratio.denom() as f32;
+        let pad = if let Some(pad) = pad.get::<gst::Pad>() {
+            pad
+        } else {
+            continue;
+        };
+
+        // And retrieve width and height from its caps
+        let caps = pad.get_current_caps().expect("Could not get current caps");
+        let (width, height, aspect_ratio) = get_geometry(&caps);
+
+        let tags = playbin
+            .emit("get-video-tags", &[&(index as i32)])
+            .expect("Could not get video tags")
+            .and_then(|tags| tags.get::<gst::TagList>());
+
+        // The language is only known if the stream has been tagged with it
+        let language = tags.as_ref().and_then(|tags| {
+            tags.get::<gst::tags::LanguageCode>()
+                .and_then(|language| language.get().map(String::from))
+        });
+
+        // Phones record in the orientation of their sensor, and tag how the video has to be
+        // rotated. The decoding pipeline applies the rotation, so we describe the rotated video.
+        let rotation = tags
+            .as_ref()
+            .and_then(|tags| {
+                tags.get::<gst::tags::ImageOrientation>()
+                    .and_then(|orientation| orientation.get().map(parse_rotation))
+            })
+            .unwrap_or(0);
+        let (width, height, aspect_ratio) = if rotation % 180 == 90 {
+            (height, width, 1.0 / aspect_ratio)
+        } else {
+            (width, height, aspect_ratio)
+        };
+
+        streams.push(Stream {
+            index,
+            id: pad.get_stream_id(),
+            language,
+            rotation,
+            width,
+            height,
+            aspect_ratio,
+            framerate: get_framerate(&caps),
+        });
+    }
+
+    let audio_streams = playbin
+        .get_property("n-audio")
+        .expect("Could not get number of audio streams")
+        .get::<i32>()
+        .expect("Could not convert number of audio streams to i32")
+        as usize;
 
     // Also, query the pipeline for the duration and convert to seconds
-    let duration_clocktime: gst::ClockTime =
-        pipeline.query_duration().expect("Could not query duration");
-    let duration = duration_clocktime
-        .nseconds()
-        .expect("Could not convert duration to nanoseconds") as f32
-        / 1_000_000_000.0;
+    let duration = query_duration(&pipeline);
+
+    // Ask whether the input can seek
+    let mut query = gst::Query::new_seeking(gst::Format::Time);
+    let seekable = if pipeline.query(&mut query) {
+        let (seekable, _, _) = query.get_result();
+        seekable
+    } else {
+        false
+    };
 
     // Stop the pipeline again
     pipeline
@@ -258,33 +1241,311 @@ fn get_meta(filename: &str) -> Result<(f32, f32), String> {
         .into_result()
         .expect("Could not stop querying pipeline");
 
-    Ok((aspect_ratio, duration))
+    Ok((streams, audio_streams, duration, seekable))
+}
+
+// Get the clockwise rotation in degrees from an image orientation tag, like "rotate-90". Mirrored
+// orientations like "flip-rotate-270" are rotated the same way.
+fn parse_rotation(orientation: &str) -> u32 {
+    orientation
+        .trim_start_matches("flip-")
+        .trim_start_matches("rotate-")
+        .parse()
+        .unwrap_or(0)
+}
+
+// Ask a paused `pipeline` for the duration of its input in seconds. Returns `None` if it's unknown.
+fn query_duration(pipeline: &gst::Pipeline) -> Option<f32> {
+    let nseconds = pipeline.query_duration::<gst::ClockTime>()?.nseconds()?;
+
+    // Some demuxers report a duration of zero instead of none at all
+    if nseconds == 0 {
+        None
+    } else {
+        Some(nseconds as f32 / 1_000_000_000.0)
+    }
+}
+
+// Get the position of `sample` in the input in seconds. Buffer timestamps don't necessarily match
+// positions in the input, so they are converted using the sample's segment.
+fn stream_time(sample: &gst::Sample) -> f32 {
+    let pts = sample
+        .get_buffer()
+        .expect("Could not get buffer from input pipeline")
+        .get_pts();
+
+    let time = sample
+        .get_segment()
+        .and_then(|segment| {
+            segment
+                .downcast_ref::<gst::ClockTime>()
+                .map(|segment| segment.to_stream_time(pts))
+        })
+        .filter(|time| time.nseconds().is_some())
+        .unwrap_or(pts);
+
+    time.nseconds()
+        .expect("Could not convert PTS to nanoseconds in input pipeline") as f32
+        / 1_000_000_000.0
+}
+
+// Get the position where the container of `sample` starts, in the same time as `stream_time`. The
+// duration the container reports is measured from there.
+fn container_start(sample: &gst::Sample) -> f32 {
+    sample
+        .get_segment()
+        .and_then(|segment| {
+            segment
+                .downcast_ref::<gst::ClockTime>()
+                .map(|segment| segment.to_stream_time(segment.get_start()))
+        })
+        .and_then(|time| time.nseconds())
+        .map_or(0.0, |nseconds| nseconds as f32 / 1_000_000_000.0)
+}
+
+// Make sure that cropping leaves at least some of the video's pixels
+fn check_crop(crop: &Crop, meta: &Meta) -> Result<(), String> {
+    if crop.left + crop.right >= meta.width || crop.top + crop.bottom >= meta.height {
+        return Err(format!(
+            "Cropping must leave some of the {}x{} pixels of the video.",
+            meta.width, meta.height
+        ));
+    }
+
+    Ok(())
+}
+
+// Get resolution and duration of the video from a paused decoding pipeline, for inputs which can't
+// be probed in advance. The resolution is taken after rotating the video.
+fn get_pipeline_meta(pipeline: &gst::Pipeline) -> Result<Meta, String> {
+    let caps = pipeline
+        .get_by_name("flip")
+        .expect("Could not get videoflip from input pipeline")
+        .get_static_pad("src")
+        .expect("Could not get source pad of videoflip")
+        .get_current_caps();
+
+    let caps = match caps {
+        Some(caps) => caps,
+        None => return Err(String::from("This does not seem to be a video file.")),
+    };
+    let (width, height, aspect_ratio) = get_geometry(&caps);
+
+    let duration = query_duration(&pipeline);
+
+    Ok(Meta {
+        width,
+        height,
+        aspect_ratio,
+        duration,
+        framerate: get_framerate(&caps),
+        stream: 0,
+        stream_id: None,
+        seekable: false,
+        audio_only: false,
+    })
+}
+
+// Find constant black borders around the video, by sampling some of its frames between `from` and
+// `to` seconds at full resolution. Only rows and columns which are black in all samples are
+// removed.
+fn detect_crop(uri: &str, meta: &Meta, from: f32, to: f32) -> Crop {
+    let (pipeline, _, appsink, _) = build_pipeline(
+        &Input::Uri(String::from(uri)),
+        Some(meta.width),
+        meta.height,
+        &Crop::default(),
+        &StreamPad::from_meta(meta),
+        &Deinterlace::default(),
+        &None,
+    );
+
+    pipeline
+        .set_state(gst::State::Paused)
+        .into_result()
+        .expect("Could not pause cropping pipeline");
+    pipeline.get_state(10 * gst::SECOND);
+
+    let mut crop: Option<Crop> = None;
+
+    for i in 0..CROP_SAMPLES {
+        // Spread the samples evenly, avoiding the very beginning and end of the range
+        let position = from + (to - from) * (i + 1) as f32 / (CROP_SAMPLES + 1) as f32;
+        if pipeline
+            .seek_simple(
+                gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT,
+                (position * 1_000_000_000.0) as u64 * gst::NSECOND,
+            )
+            .is_err()
+        {
+            break;
+        }
+        pipeline.get_state(10 * gst::SECOND);
+
+        let sample = match appsink.pull_preroll() {
+            Some(sample) => sample,
+            None => break,
+        };
+        let frame = frame::Frame {
+            buffer: sample
+                .get_buffer()
+                .expect("Could not get buffer from cropping pipeline"),
+            width: meta.width,
+            height: meta.height,
+            pts: None,
+        };
+
+        // Completely black frames, like fades, don't tell us anything about the borders
+        if let Some(borders) = find_borders(&frame) {
+            crop = Some(match crop {
+                Some(crop) => Crop {
+                    top: cmp::min(crop.top, borders.top),
+                    right: cmp::min(crop.right, borders.right),
+                    bottom: cmp::min(crop.bottom, borders.bottom),
+                    left: cmp::min(crop.left, borders.left),
+                },
+                None => borders,
+            });
+        }
+    }
+
+    pipeline
+        .set_state(gst::State::Null)
+        .into_result()
+        .expect("Could not stop cropping pipeline");
+
+    crop.unwrap_or_default()
+}
+
+// Count the black rows and columns at each side of `frame`. Returns `None` if the frame is black
+// entirely.
+fn find_borders(frame: &frame::Frame) -> Option<Crop> {
+    let map = frame
+        .buffer
+        .map_readable()
+        .expect("Could not get readable map of buffer for cropping");
+    let data = map.as_slice();
+
+    let luma = |x: usize, y: usize| {
+        let i = (y * frame.width + x) * 4;
+        (29 * data[i] as usize + 150 * data[i + 1] as usize + 77 * data[i + 2] as usize) / 256
+    };
+    let black_row = |y: usize| {
+        (0..frame.width).map(|x| luma(x, y)).sum::<usize>() < BLACK_THRESHOLD * frame.width
+    };
+    let black_column = |x: usize| {
+        (0..frame.height).map(|y| luma(x, y)).sum::<usize>() < BLACK_THRESHOLD * frame.height
+    };
+
+    let top = (0..frame.height).take_while(|&y| black_row(y)).count();
+    if top == frame.height {
+        return None;
+    }
+    let bottom = (0..frame.height)
+        .rev()
+        .take_while(|&y| black_row(y))
+        .count();
+    let left = (0..frame.width).take_while(|&x| black_column(x)).count();
+    let right = (0..frame.width)
+        .rev()
+        .take_while(|&x| black_column(x))
+        .count();
+
+    Some(Crop {
+        top,
+        right,
+        bottom,
+        left,
+    })
 }
 
-// Build a pipeline that decodes the video to BGRx at 1 FPS, scales the frames to thumbnail size,
-// and hands it to an Appsink
+// Build a pipeline that decodes the video to BGRx at 1 FPS, removes the `crop` borders, scales the
+// frames to thumbnail size, and hands it to an Appsink. If `output_width` is `None`, it is chosen so
+// that the aspect ratio is kept. If audio `levels` are given, the audio track is decoded as well,
+// and measured into them, by a branch whose first element is returned last.
 fn build_pipeline(
-    filename: &str,
-    output_width: usize,
+    input: &Input,
+    output_width: Option<usize>,
     output_height: usize,
-) -> (gst::Pipeline, gst::Element, gst_app::AppSink) {
-    let uri = format!(
-        "file://{}",
-        fs::canonicalize(&PathBuf::from(filename))
-            .expect("Could not canonicalize input filename")
-            .to_str()
-            .expect("Could not convert canonicalized input filename to str")
-    );
+    crop: &Crop,
+    stream_pad: &StreamPad,
+    deinterlace: &Deinterlace,
+    levels: &Option<Arc<Mutex<audio::Levels>>>,
+) -> (
+    gst::Pipeline,
+    gst::Element,
+    gst_app::AppSink,
+    Option<gst::Element>,
+) {
+    // `src` is the element which provides the decoded video on a dynamic pad. For image
+    // sequences, `filesrc` reads the files which it decodes.
+    let (src, filesrc) = match input {
+        Input::Uri(uri) => (make_uridecodebin(uri), None),
+        Input::Sequence(sequence, framerate) => {
+            let filesrc = gst::ElementFactory::make("multifilesrc", None)
+                .expect("Could not create multifilesrc");
+            filesrc
+                .set_property("location", &sequence.location)
+                .expect("Could not set location property on multifilesrc");
+            filesrc
+                .set_property("index", &(sequence.first as i32))
+                .expect("Could not set index property on multifilesrc");
+            filesrc
+                .set_property("stop-index", &(sequence.last as i32))
+                .expect("Could not set stop-index property on multifilesrc");
+            // The decoders timestamp the images according to the framerate in these caps
+            filesrc
+                .set_property(
+                    "caps",
+                    &gst::Caps::new_simple(sequence.media_type(), &[("framerate", framerate)]),
+                )
+                .expect("Could not set caps property on multifilesrc");
 
-    let src =
-        gst::ElementFactory::make("uridecodebin", None).expect("Could not create uridecodebin");
-    src.set_property("uri", &uri)
-        .expect("Could not set property on uridecodebin");
+            let src =
+                gst::ElementFactory::make("decodebin", None).expect("Could not create decodebin");
+            (src, Some(filesrc))
+        }
+    };
 
     let videoconvert =
         gst::ElementFactory::make("videoconvert", None).expect("Could not create videoconvert");
+    // In "auto" mode, the deinterlacer looks at the caps, and only touches interlaced frames.
+    // Otherwise, thumbnails of broadcast recordings show combing artifacts.
+    let deinterlacer =
+        gst::ElementFactory::make("deinterlace", None).expect("Could not create deinterlace");
+    let mode = match deinterlace.enabled {
+        None => "auto",
+        Some(true) => "interlaced",
+        Some(false) => "disabled",
+    };
+    deinterlacer.set_property_from_str("mode", mode);
+    if let Some(ref method) = deinterlace.method {
+        deinterlacer.set_property_from_str("method", method);
+    }
+    // Rotate the video according to its orientation tag
+    let videoflip =
+        gst::ElementFactory::make("videoflip", "flip").expect("Could not create videoflip");
+    videoflip.set_property_from_str("method", "automatic");
+    let videocrop =
+        gst::ElementFactory::make("videocrop", None).expect("Could not create videocrop");
+    videocrop
+        .set_property("top", &(crop.top as i32))
+        .expect("Could not set top property on videocrop");
+    videocrop
+        .set_property("right", &(crop.right as i32))
+        .expect("Could not set right property on videocrop");
+    videocrop
+        .set_property("bottom", &(crop.bottom as i32))
+        .expect("Could not set bottom property on videocrop");
+    videocrop
+        .set_property("left", &(crop.left as i32))
+        .expect("Could not set left property on videocrop");
     let videorate =
         gst::ElementFactory::make("videorate", None).expect("Could not convert videorate");
+    // Otherwise, videorate fills the gap before the first frame with copies of it
+    videorate
+        .set_property("skip-to-first", &true)
+        .expect("Could not set property on videorate");
     let videoscale =
         gst::ElementFactory::make("videoscale", None).expect("Could not convert videoscale");
     // Scale frames exactly to the desired size, don't add borders
@@ -298,19 +1559,29 @@ fn build_pipeline(
 
     let capsfilter = gst::ElementFactory::make("capsfilter", method.as_str())
         .expect("Could not create input capsfilter");
+    let caps = match output_width {
+        Some(output_width) => gst::Caps::new_simple(
+            "video/x-raw",
+            &[
+                ("format", &"BGRx"),
+                ("framerate", &gst::Fraction::new(1, 1)),
+                ("width", &(output_width as i32)),
+                ("height", &(output_height as i32)),
+            ],
+        ),
+        // With square pixels, videoscale picks the width which keeps the display aspect ratio
+        None => gst::Caps::new_simple(
+            "video/x-raw",
+            &[
+                ("format", &"BGRx"),
+                ("framerate", &gst::Fraction::new(1, 1)),
+                ("height", &(output_height as i32)),
+                ("pixel-aspect-ratio", &gst::Fraction::new(1, 1)),
+            ],
+        ),
+    };
     capsfilter
-        .set_property(
-            "caps",
-            &gst::Caps::new_simple(
-                "video/x-raw",
-                &[
-                    ("format", &"BGRx"),
-                    ("framerate", &gst::Fraction::new(1, 1)),
-                    ("width", &(output_width as i32)),
-                    ("height", &(output_height as i32)),
-                ],
-            ),
-        )
+        .set_property("caps", &caps)
         .expect("Could not set properties on input capsfilter");
 
     let sink = gst::ElementFactory::make("appsink", None).expect("Could not create input appsink");
@@ -321,6 +1592,9 @@ fn build_pipeline(
         .add_many(&[
             &src,
             &videoconvert,
+            &deinterlacer,
+            &videoflip,
+            &videocrop,
             &videorate,
             &videoscale,
             &capsfilter,
@@ -328,8 +1602,26 @@ fn build_pipeline(
         ])
         .expect("Could not add elements to input pipeline");
 
-    gst::Element::link_many(&[&videoconvert, &videorate, &videoscale, &capsfilter, &sink])
-        .expect("Could not link input pipeline");
+    if let Some(filesrc) = filesrc {
+        pipeline
+            .add(&filesrc)
+            .expect("Could not add multifilesrc to input pipeline");
+        filesrc
+            .link(&src)
+            .expect("Could not link multifilesrc to decodebin");
+    }
+
+    gst::Element::link_many(&[
+        &videoconvert,
+        &deinterlacer,
+        &videoflip,
+        &videocrop,
+        &videorate,
+        &videoscale,
+        &capsfilter,
+        &sink,
+    ])
+    .expect("Could not link input pipeline");
 
     let appsink = sink
         .clone()
@@ -340,13 +1632,42 @@ fn build_pipeline(
         .set_property("sync", &false)
         .expect("Could not set property on input appsink");
 
+    let audio_elements = levels
+        .as_ref()
+        .map(|levels| build_audio_branch(&pipeline, levels));
+    let audioconvert = audio_elements.as_ref().map(|elements| elements[0].clone());
+    let audio_branch = audioconvert.clone();
+
     // When a new source pad opens on the decodebin, connect it to the videoconvert element.
     // this code is required because media files might contain no (or many) video strems, this is
-    // not known before the pipeline is started.
+    // not known before the pipeline is started. Only the pad of the selected stream is connected,
+    // which is recognized by its identifier, or else by counting the video pads. The first audio
+    // stream is connected to the audio branch, if there is one.
     let convert_clone = videoconvert.clone();
+    let stream_pad = stream_pad.clone();
+    let video_pads = AtomicUsize::new(0);
     src.connect_pad_added(move |_, src_pad| {
         let convert = &convert_clone;
 
+        let new_pad_caps = src_pad
+            .get_current_caps()
+            .expect("Failed to get caps of new pad.");
+        let new_pad_struct = new_pad_caps
+            .get_structure(0)
+            .expect("Failed to get first structure of caps.");
+        let new_pad_type = new_pad_struct.get_name();
+
+        if let (true, Some(audioconvert)) = (new_pad_type.starts_with("audio/x-raw"), &audioconvert)
+        {
+            let sink_pad = audioconvert
+                .get_static_pad("sink")
+                .expect("Failed to get static sink pad from audioconvert");
+            if !sink_pad.is_linked() && src_pad.link(&sink_pad) != gst::PadLinkReturn::Ok {
+                println!("Type is {} but link failed.", new_pad_type);
+            }
+            return;
+        }
+
         let sink_pad = convert
             .get_static_pad("sink")
             .expect("Failed to get static sink pad from convert");
@@ -356,16 +1677,8 @@ fn build_pipeline(
             return;
         }
 
-        let new_pad_caps = src_pad
-            .get_current_caps()
-            .expect("Failed to get caps of new pad.");
-        let new_pad_struct = new_pad_caps
-            .get_structure(0)
-            .expect("Failed to get first structure of caps.");
-        let new_pad_type = new_pad_struct.get_name();
-
-        let is_audio = new_pad_type.starts_with("video/x-raw");
-        if !is_audio {
+        let is_video = new_pad_type.starts_with("video/x-raw");
+        if !is_video {
             println!(
                 "It has type {} which is not raw video. Ignoring.",
                 new_pad_type
@@ -373,11 +1686,276 @@ fn build_pipeline(
             return;
         }
 
+        // Skip video streams other than the selected one
+        let video_pad = video_pads.fetch_add(1, Ordering::SeqCst);
+        let selected = match stream_pad {
+            StreamPad::Id(ref id) => src_pad.get_stream_id().as_ref() == Some(id),
+            StreamPad::Index(index) => video_pad == index,
+        };
+        if !selected {
+            return;
+        }
+
         let ret = src_pad.link(&sink_pad);
         if ret != gst::PadLinkReturn::Ok {
             println!("Type is {} but link failed.", new_pad_type);
         }
     });
 
-    (pipeline, capsfilter, appsink)
+    // Without an audio stream, the audio sink would keep the pipeline from prerolling and from
+    // reaching its end, so the audio branch is removed again
+    if let Some(elements) = audio_elements {
+        src.connect_no_more_pads(move |_| {
+            let linked = elements[0]
+                .get_static_pad("sink")
+                .expect("Failed to get static sink pad from audioconvert")
+                .is_linked();
+            if linked {
+                return;
+            }
+
+            if let Some(bin) = elements[0]
+                .get_parent()
+                .and_then(|parent| parent.downcast::<gst::Bin>().ok())
+            {
+                for element in &elements {
+                    element.set_state(gst::State::Null);
+                }
+                bin.remove_many(&elements.iter().collect::<Vec<&gst::Element>>())
+                    .expect("Could not remove audio elements from input pipeline");
+            }
+        });
+    }
+
+    (pipeline, capsfilter, appsink, audio_branch)
+}
+
+// Add elements to `pipeline` which convert audio to mono samples, and measure them into `levels`.
+// Returns the elements, the first of which the audio stream has to be linked to.
+fn build_audio_branch(
+    pipeline: &gst::Pipeline,
+    levels: &Arc<Mutex<audio::Levels>>,
+) -> Vec<gst::Element> {
+    let (elements, appsink) = add_audio_elements(pipeline);
+
+    // Measure the samples as they arrive, so that the audio doesn't hold up the video
+    let levels = Arc::clone(levels);
+    appsink.set_callbacks(
+        gst_app::AppSinkCallbacks::new()
+            .new_sample(move |appsink| {
+                let sample = match appsink.pull_sample() {
+                    Some(sample) => sample,
+                    None => return gst::FlowReturn::Eos,
+                };
+
+                let (rate, samples) = audio_samples(&sample);
+                levels.lock().expect("Could not lock audio levels").add(
+                    stream_time(&sample),
+                    rate,
+                    &samples,
+                );
+                gst::FlowReturn::Ok
+            })
+            .build(),
+    );
+
+    elements
+}
+
+// Build a pipeline that decodes the first audio stream of `uri` to mono samples, and hands them to
+// an Appsink
+fn build_audio_pipeline(uri: &str) -> (gst::Pipeline, gst_app::AppSink) {
+    let src = make_uridecodebin(uri);
+
+    let pipeline = gst::Pipeline::new(None);
+    pipeline
+        .add(&src)
+        .expect("Could not add uridecodebin to input pipeline");
+    let (elements, appsink) = add_audio_elements(&pipeline);
+
+    let audioconvert = elements[0].clone();
+    src.connect_pad_added(move |_, src_pad| {
+        let sink_pad = audioconvert
+            .get_static_pad("sink")
+            .expect("Failed to get static sink pad from audioconvert");
+
+        let new_pad_caps = src_pad
+            .get_current_caps()
+            .expect("Failed to get caps of new pad.");
+        let new_pad_type = new_pad_caps
+            .get_structure(0)
+            .expect("Failed to get first structure of caps.")
+            .get_name();
+
+        if sink_pad.is_linked() || !new_pad_type.starts_with("audio/x-raw") {
+            return;
+        }
+
+        if src_pad.link(&sink_pad) != gst::PadLinkReturn::Ok {
+            println!("Type is {} but link failed.", new_pad_type);
+        }
+    });
+
+    (pipeline, appsink)
+}
+
+// Add elements to `pipeline` which convert audio to mono samples, and hand them to an Appsink.
+// Returns the elements, the first of which the audio stream has to be linked to, and the Appsink.
+fn add_audio_elements(pipeline: &gst::Pipeline) -> (Vec<gst::Element>, gst_app::AppSink) {
+    let audioconvert =
+        gst::ElementFactory::make("audioconvert", None).expect("Could not create audioconvert");
+    let capsfilter =
+        gst::ElementFactory::make("capsfilter", None).expect("Could not create audio capsfilter");
+    capsfilter
+        .set_property(
+            "caps",
+            &gst::Caps::new_simple(
+                "audio/x-raw",
+                &[
+                    ("format", &"F32LE"),
+                    ("layout", &"interleaved"),
+                    ("channels", &1),
+                ],
+            ),
+        )
+        .expect("Could not set properties on audio capsfilter");
+    let sink = gst::ElementFactory::make("appsink", None).expect("Could not create audio appsink");
+
+    pipeline
+        .add_many(&[&audioconvert, &capsfilter, &sink])
+        .expect("Could not add audio elements to input pipeline");
+    gst::Element::link_many(&[&audioconvert, &capsfilter, &sink])
+        .expect("Could not link audio elements");
+
+    let appsink = sink
+        .clone()
+        .dynamic_cast::<gst_app::AppSink>()
+        .expect("Audio sink element is expected to be an appsink!");
+    appsink
+        .set_property("sync", &false)
+        .expect("Could not set property on audio appsink");
+
+    (vec![audioconvert, capsfilter, sink], appsink)
+}
+
+// Get the sample rate and the mono samples of an audio `sample` from `add_audio_elements`
+fn audio_samples(sample: &gst::Sample) -> (u32, Vec<f32>) {
+    let rate = sample
+        .get_caps()
+        .and_then(|caps| {
+            caps.get_structure(0)
+                .and_then(|structure| structure.get_value("rate"))
+                .and_then(|rate| rate.get::<i32>())
+        })
+        .unwrap_or(0);
+
+    let buffer = sample
+        .get_buffer()
+        .expect("Could not get buffer from audio appsink");
+    let map = buffer
+        .map_readable()
+        .expect("Could not get readable map of audio buffer");
+    let samples = map
+        .as_slice()
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+
+    (cmp::max(rate, 0) as u32, samples)
+}
+
+// Create a uridecodebin element, which opens `uri`, and provides its decoded streams on dynamic
+// pads
+fn make_uridecodebin(uri: &str) -> gst::Element {
+    let src =
+        gst::ElementFactory::make("uridecodebin", None).expect("Could not create uridecodebin");
+    src.set_property("uri", &uri)
+        .expect("Could not set property on uridecodebin");
+    // Buffer network streams on disk, which allows demuxers to seek in the downloaded part even if
+    // the server doesn't support range requests
+    if !uri.starts_with("file://") {
+        src.set_property("download", &true)
+            .expect("Could not set download property on uridecodebin");
+    }
+    src
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{select_stream, Stream};
+
+    fn stream(index: usize, id: &str, language: &str) -> Stream {
+        Stream {
+            index,
+            id: Some(String::from(id)),
+            language: Some(String::from(language)),
+            rotation: 0,
+            width: 320,
+            height: 240,
+            aspect_ratio: 4.0 / 3.0,
+            framerate: Some(25.0),
+        }
+    }
+
+    #[test]
+    fn test_is_uri() {
+        use crate::source::is_uri;
+        assert!(is_uri("https://example.com/video.mp4"));
+        assert!(is_uri("rtsp://example.com:554/stream"));
+        assert!(is_uri("file:///tmp/video.mp4"));
+        assert!(!is_uri("video.mp4"));
+        assert!(!is_uri("/tmp/video.mp4"));
+        assert!(!is_uri("://video.mp4"));
+        assert!(!is_uri("my video://.mp4"));
+        assert!(!is_uri("C:\\Videos\\video.mp4"));
+    }
+
+    #[test]
+    fn test_select_stream() {
+        let streams = vec![stream(0, "abc/001", "eng"), stream(2, "abc/003", "deu")];
+
+        assert_eq!(select_stream(&streams, "2").map(|s| s.index), Ok(2));
+        assert_eq!(select_stream(&streams, "abc/001").map(|s| s.index), Ok(0));
+        assert_eq!(select_stream(&streams, "deu").map(|s| s.index), Ok(2));
+        assert!(select_stream(&streams, "1").is_err());
+        assert!(select_stream(&streams, "3").is_err());
+        assert!(select_stream(&streams, "fra").is_err());
+    }
+
+    #[test]
+    fn test_processed_range() {
+        use crate::source::{processed_range, Deinterlace, Options};
+        let options = Options {
+            crop: None,
+            stream: None,
+            start: 10.0,
+            end: None,
+            ignore_duration: false,
+            deinterlace: Deinterlace::default(),
+            framerate: (25, 1),
+            audio: false,
+            min_framerate: None,
+        };
+
+        // The first frame is at the start of the container, or 5 seconds after it
+        assert_eq!(
+            processed_range(&options, Some(60.0), 0.0),
+            Ok((10.0, Some(60.0), Some(60.0)))
+        );
+        assert_eq!(
+            processed_range(&options, Some(60.0), 5.0),
+            Ok((10.0, Some(55.0), Some(55.0)))
+        );
+        assert!(processed_range(&options, Some(60.0), 50.0).is_err());
+        assert_eq!(processed_range(&options, None, 0.0), Ok((10.0, None, None)));
+    }
+
+    #[test]
+    fn test_parse_rotation() {
+        use crate::source::parse_rotation;
+        assert_eq!(parse_rotation("rotate-0"), 0);
+        assert_eq!(parse_rotation("rotate-90"), 90);
+        assert_eq!(parse_rotation("flip-rotate-270"), 270);
+        assert_eq!(parse_rotation("sideways"), 0);
+    }
 }
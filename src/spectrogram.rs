@@ -0,0 +1,334 @@
+use crate::audio;
+use crate::frame;
+use std::cmp;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+// Number of samples each spectrum is computed from. At 44.1 kHz, this resolves about 21 Hz.
+const FFT_SIZE: usize = 2048;
+
+// Frequency range shown by the spectrogram, in Hz. Rows are spaced logarithmically, so that each
+// octave gets the same height, like pitch is perceived.
+const MIN_FREQUENCY: f32 = 30.0;
+const MAX_FREQUENCY: f32 = 20_000.0;
+
+// Range of energies which is mapped to colors, in dB relative to a full scale sine
+const MIN_LEVEL: f32 = -90.0;
+const MAX_LEVEL: f32 = -10.0;
+
+// Colors from quiet to loud, as RGB
+const COLORS: [[u8; 3]; 5] = [
+    [0, 0, 0],
+    [40, 10, 90],
+    [150, 30, 120],
+    [240, 110, 30],
+    [255, 240, 160],
+];
+
+// Turns audio samples into the columns of a spectrogram, which show how the energy of the audio is
+// distributed over frequencies: high frequencies at the top, low ones at the bottom, and the
+// energy as color. Each column summarizes a fixed interval of the audio.
+pub struct Spectrogram {
+    // Length of the audio summarized in one column, in seconds
+    interval: f32,
+    // Position where the first column starts, in seconds. Earlier samples are ignored.
+    origin: f32,
+    // Height of the columns in pixels
+    height: usize,
+    // Hann window, which is applied to the samples before transforming them
+    window: Vec<f32>,
+    // Cosine and sine of the angles the FFT needs
+    twiddles: Vec<(f32, f32)>,
+
+    // Index of the column which is currently filled
+    column: Option<usize>,
+    // Sample rate of the current column
+    rate: u32,
+    // Samples of the current column which weren't transformed yet
+    block: Vec<f32>,
+    // Sum of the power spectra of the current column, and how many were summed
+    power: Vec<f32>,
+    spectra: usize,
+    // Finished columns, as their positions and the level of each row in dB
+    done: VecDeque<(f32, Vec<f32>)>,
+}
+
+impl Spectrogram {
+    // Initializes a spectrogram whose columns are `height` pixels high, and each summarize
+    // `interval` seconds of audio, starting at `origin`
+    pub fn new(interval: f32, origin: f32, height: usize) -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let twiddles = (0..FFT_SIZE / 2)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f32 / FFT_SIZE as f32;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+
+        Self {
+            interval,
+            origin,
+            height,
+            window,
+            twiddles,
+            column: None,
+            rate: 0,
+            block: Vec::with_capacity(FFT_SIZE),
+            power: vec![0.0; FFT_SIZE / 2 + 1],
+            spectra: 0,
+            done: VecDeque::new(),
+        }
+    }
+
+    // Add mono `samples` with the given sample `rate`, the first of which is at `position` seconds
+    pub fn add(&mut self, position: f32, rate: u32, samples: &[f32]) {
+        if rate == 0 {
+            return;
+        }
+
+        for (i, &sample) in samples.iter().enumerate() {
+            let offset = f64::from(position - self.origin) + i as f64 / f64::from(rate);
+            if offset < 0.0 {
+                continue;
+            }
+
+            let index = (offset / f64::from(self.interval)) as usize;
+            if self.column != Some(index) {
+                self.finish_column();
+                self.column = Some(index);
+            }
+
+            self.rate = rate;
+            self.block.push(sample);
+            if self.block.len() == FFT_SIZE {
+                self.transform();
+            }
+        }
+    }
+
+    // Returns the next finished column, and its position in seconds
+    pub fn next_column(&mut self) -> Option<(f32, frame::Frame)> {
+        self.done
+            .pop_front()
+            .map(|(position, levels)| (position, self.render(&levels)))
+    }
+
+    // Finish the column which is currently filled, because no more samples will arrive. Returns
+    // the next finished column, like `next_column`.
+    pub fn finish(&mut self) -> Option<(f32, frame::Frame)> {
+        self.finish_column();
+        self.next_column()
+    }
+
+    // Add the power spectrum of the samples in `block` to the current column
+    fn transform(&mut self) {
+        let mut re: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| {
+                self.block
+                    .get(i)
+                    .map_or(0.0, |sample| sample * self.window[i])
+            })
+            .collect();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im, &self.twiddles);
+
+        for (bin, power) in self.power.iter_mut().enumerate() {
+            *power += re[bin] * re[bin] + im[bin] * im[bin];
+        }
+        self.spectra += 1;
+        self.block.clear();
+    }
+
+    // Calculate the levels of the rows of the current column, and queue them
+    fn finish_column(&mut self) {
+        let index = match self.column.take() {
+            Some(index) => index,
+            None => return,
+        };
+
+        // A short rest only counts if there is nothing else, otherwise it's mostly padding
+        if !self.block.is_empty() && (self.spectra == 0 || self.block.len() >= FFT_SIZE / 2) {
+            self.transform();
+        }
+        self.block.clear();
+
+        // A full scale sine reaches this power in its bin, because of the window
+        let full_scale = (self.window.iter().sum::<f32>() / 2.0).powi(2);
+        let spectra = cmp::max(self.spectra, 1) as f32;
+
+        let max_frequency = MAX_FREQUENCY.min(self.rate as f32 / 2.0);
+        let bins = FFT_SIZE / 2 + 1;
+        let levels = (0..self.height)
+            .map(|y| {
+                // Rows are counted from the bottom
+                let row = (self.height - 1 - y) as f32;
+                let frequency = |row: f32| {
+                    MIN_FREQUENCY * (max_frequency / MIN_FREQUENCY).powf(row / self.height as f32)
+                };
+                let bin = |frequency: f32| frequency * FFT_SIZE as f32 / self.rate as f32;
+
+                let from = cmp::min(bin(frequency(row)) as usize, bins - 1);
+                let to = cmp::min(
+                    cmp::max(bin(frequency(row + 1.0)).ceil() as usize, from + 1),
+                    bins,
+                );
+
+                // The loudest bin in the row, so that single tones don't fade in wide rows
+                let power = self.power[from..to].iter().cloned().fold(0.0, f32::max);
+                10.0 * (power / spectra / full_scale).max(1e-20).log10()
+            })
+            .collect();
+
+        let position = self.origin + (index as f32 + 0.5) * self.interval;
+        self.done.push_back((position, levels));
+
+        for power in self.power.iter_mut() {
+            *power = 0.0;
+        }
+        self.spectra = 0;
+    }
+
+    // Draw a column which is 1 pixel wide, with the given `levels` of its rows
+    fn render(&self, levels: &[f32]) -> frame::Frame {
+        let mut column = frame::Frame::new(1, self.height);
+
+        {
+            let buffer = column
+                .buffer
+                .get_mut()
+                .expect("Could not get mutable buffer for spectrogram");
+            let mut data = buffer
+                .map_writable()
+                .expect("Could not get writable map of buffer for spectrogram");
+
+            for (y, level) in levels.iter().enumerate() {
+                let color = color(*level);
+                // Frames are stored as BGRx
+                data[y * 4] = color[2];
+                data[y * 4 + 1] = color[1];
+                data[y * 4 + 2] = color[0];
+                data[y * 4 + 3] = 255;
+            }
+        }
+
+        column
+    }
+}
+
+// Returns the color of a `level` in dB, from the gradient in `COLORS`
+fn color(level: f32) -> [u8; 3] {
+    let t = ((level - MIN_LEVEL) / (MAX_LEVEL - MIN_LEVEL))
+        .max(0.0)
+        .min(1.0);
+    let scaled = t * (COLORS.len() - 1) as f32;
+    let i = cmp::min(scaled as usize, COLORS.len() - 2);
+    audio::mix(COLORS[i], COLORS[i + 1], scaled - i as f32)
+}
+
+// Replace `re` and `im` by their discrete Fourier transform, using the iterative radix-2 FFT. The
+// length must be a power of two, and `twiddles` must contain cosine and sine of -2πk/length for
+// the first half of the k.
+fn fft(re: &mut [f32], im: &mut [f32], twiddles: &[(f32, f32)]) {
+    let n = re.len();
+
+    // Bring the elements into bit-reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    // Combine transforms of increasing length
+    let mut length = 2;
+    while length <= n {
+        let step = n / length;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (cos, sin) = twiddles[k * step];
+                let a = start + k;
+                let b = a + length / 2;
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        length *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_fft() {
+        use crate::spectrogram::fft;
+
+        let n = 16;
+        let twiddles: Vec<(f32, f32)> = (0..n / 2)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f32 / n as f32;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+
+        // A cosine with three periods has all its energy in bins 3 and 13
+        let mut re: Vec<f32> = (0..n)
+            .map(|i| (2.0 * PI * 3.0 * i as f32 / n as f32).cos())
+            .collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im, &twiddles);
+
+        for bin in 0..n {
+            let magnitude = (re[bin] * re[bin] + im[bin] * im[bin]).sqrt();
+            let expected = if bin == 3 || bin == 13 { 8.0 } else { 0.0 };
+            assert!((magnitude - expected).abs() < 1e-3, "bin {}", bin);
+        }
+    }
+
+    #[test]
+    fn test_spectrogram() {
+        use crate::spectrogram::Spectrogram;
+
+        // Two columns of a full scale 1 kHz sine
+        let samples: Vec<f32> = (0..9600)
+            .map(|i| (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin())
+            .collect();
+        let mut spectrogram = Spectrogram::new(0.1, 0.0, 100);
+        spectrogram.add(0.0, 48000, &samples);
+        spectrogram.finish_column();
+
+        assert_eq!(spectrogram.done.len(), 2);
+        let (position, levels) = &spectrogram.done[0];
+        assert_eq!(*position, 0.05);
+
+        // 1 kHz is a bit above the middle. It falls between two bins, so its level is a bit
+        // below full scale.
+        let (loudest, level) =
+            levels
+                .iter()
+                .enumerate()
+                .fold((0, -1000.0), |loudest, (y, &level)| {
+                    if level > loudest.1 {
+                        (y, level)
+                    } else {
+                        loudest
+                    }
+                });
+        assert!((45..=47).contains(&loudest), "loudest row was {}", loudest);
+        assert!(level < 0.0 && level > -1.5, "level was {}", level);
+    }
+}
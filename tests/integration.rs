@@ -10,9 +10,16 @@ mod integration {
     use assert_cmd::prelude::*;
     use assert_fs::prelude::*;
     use predicates::prelude::*;
+    use std::collections::hash_map::DefaultHasher;
     use std::env;
+    use std::fs;
+    use std::hash::{Hash, Hasher};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
     use std::path::Path;
-    use std::process::Command;
+    use std::process::{self, Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
 
     #[test]
     fn basics() {
@@ -82,6 +89,877 @@ mod integration {
         ok_with_file("-w 16 -h 10000");
     }
 
+    #[test]
+    fn bands() {
+        fail_with_file("--bands");
+        fail_with_file("--bands foo");
+        fail_with_file("--bands 0");
+        fail_with_file("--bands -1");
+        fail_with_file("-h 16 --bands 17");
+        fail_with_file("--bands 3 --flat");
+
+        ok_with_file("--bands 1");
+        ok_with_file("--bands 3");
+        ok_with_file("-h 16 --bands 16");
+        ok_with_file("--flat");
+    }
+
+    #[test]
+    fn stream() {
+        fail_with_file("--stream");
+        fail_with_file("--stream 1");
+        fail_with_file("--stream nope");
+
+        ok_with_file("--stream 0");
+        ok_with_file("--list-streams");
+    }
+
+    #[test]
+    fn range() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for range");
+        let vtt_file = tmp_dir.child("test.vtt");
+        let vtt_filename = vtt_file
+            .path()
+            .to_str()
+            .expect("Could not convert VTT filename to str");
+
+        fail_with_file("--start");
+        fail_with_file("--start foo");
+        fail_with_file("--start -1");
+        fail_with_file("--start 1:60");
+        fail_with_file("--start 100");
+        fail_with_file("--start 0.2 --end 0.1");
+        fail_with_file("--end 0");
+        fail_with_file("--end 0.5 --duration 0.2");
+
+        ok_with_file("--start 0.2");
+        ok_with_file("--end 0.4");
+        ok_with_file("--start 0.1 --duration 0.3");
+        ok_with_file("--start 00:00.1 --end 00:00.5");
+
+        ok_with_file(&format!(
+            "--start 0.5 --end 100 -w 160 --thumbnails {}",
+            vtt_filename
+        ));
+        vtt_file.assert(
+            predicate::str::contains("00:00.500 --> ")
+                .from_utf8()
+                .from_file_path(),
+        );
+
+        ok_with_file(&format!(
+            "--start 0.5 -w 160 --relative-timestamps --thumbnails {}",
+            vtt_filename
+        ));
+        vtt_file.assert(
+            predicate::str::contains("00:00.000 --> ")
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn uri() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for URI");
+        let timeline_file = tmp_dir.child("test.jpg");
+        let timeline_filename = timeline_file
+            .path()
+            .to_str()
+            .expect("Could not convert timeline filename to str");
+
+        let url = serve_test_file();
+
+        fail(&format!("{}/does_not_exist.mkv", url));
+        fail("nope://nothing");
+
+        ok(&format!(
+            "{}/timelens_test.mkv --timeline {}",
+            url, timeline_filename
+        ));
+        timeline_file.assert(predicate::path::is_file());
+
+        // The server doesn't support range requests, so this can't seek
+        ok(&format!(
+            "{}/timelens_test.mkv --start 0.2 --timeline {}",
+            url, timeline_filename
+        ));
+    }
+
+    #[test]
+    fn unknown_duration() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for duration");
+        let vtt_file = tmp_dir.child("test.vtt");
+        let vtt_filename = vtt_file
+            .path()
+            .to_str()
+            .expect("Could not convert VTT filename to str");
+
+        fail_with_file("--ignore-duration --start 100");
+
+        ok_with_file("--ignore-duration");
+        ok_with_file("--ignore-duration --start 0.2");
+        ok_with_file("--ignore-duration --end 0.4");
+
+        ok_with_file(&format!(
+            "--ignore-duration -w 160 --thumbnails {}",
+            vtt_filename
+        ));
+        vtt_file.assert(
+            predicate::str::contains("00:00.000 --> ")
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn timestamp_offset() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for offset");
+        let vtt_file = tmp_dir.child("test.vtt");
+        let vtt_filename = vtt_file
+            .path()
+            .to_str()
+            .expect("Could not convert VTT filename to str");
+
+        // The frames of this file start at ten seconds, but the whole timeline should be used
+        let filename = fixture(
+            "offset.mkv",
+            "videotestsrc num-buffers=20 timestamp-offset=10000000000 ! videoconvert ! \
+             vp8enc ! matroskamux ! filesink location={}",
+        );
+
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for offset")
+            .args(&["-w", "160", "--thumbnails", vtt_filename, &filename])
+            .assert()
+            .success();
+        vtt_file.assert(
+            predicate::str::contains("00:00.000 --> ")
+                .and(predicate::str::contains(" --> 00:00."))
+                .and(predicate::str::contains("00:10.").not())
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn rotation() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for rotation");
+        let manifest_file = tmp_dir.child("test.json");
+        let manifest_filename = manifest_file
+            .path()
+            .to_str()
+            .expect("Could not convert manifest filename to str");
+
+        // This file is recorded in landscape, but has to be displayed in portrait
+        let filename = fixture(
+            "rotated.mp4",
+            "videotestsrc num-buffers=20 ! video/x-raw,width=320,height=240 ! \
+             taginject tags=\"image-orientation=rotate-90\" ! videoconvert ! jpegenc ! \
+             qtmux ! filesink location={}",
+        );
+
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for rotation")
+            .args(&["--manifest", manifest_filename, &filename])
+            .assert()
+            .success();
+        manifest_file.assert(
+            predicate::str::contains("\"width\": 240,")
+                .and(predicate::str::contains("\"height\": 320,"))
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn concatenate() {
+        assert_test_file_exists();
+
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for concatenate");
+        let vtt_file = tmp_dir.child("test.vtt");
+        let vtt_filename = vtt_file
+            .path()
+            .to_str()
+            .expect("Could not convert VTT filename to str");
+        let list_file = tmp_dir.child("list.m3u");
+        let list_filename = list_file
+            .path()
+            .to_str()
+            .expect("Could not convert list filename to str");
+        let filename = test_file_name();
+
+        // Both parts are about 0.67 seconds long, so the cues should span more than a second
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for concatenate")
+            .args(&[
+                "-w",
+                "160",
+                "--thumbnails",
+                vtt_filename,
+                &filename,
+                &filename,
+            ])
+            .assert()
+            .success();
+        vtt_file.assert(
+            predicate::str::contains(" --> 00:01.")
+                .from_utf8()
+                .from_file_path(),
+        );
+
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for concatenate, part 2")
+            .args(&["--start", "0.2", &filename, &filename])
+            .assert()
+            .failure();
+
+        list_file
+            .write_str(&format!("#EXTM3U\n{}\n{}\n", filename, filename))
+            .expect("Could not write list");
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for concatenate, part 3")
+            .args(&[
+                "--timeline",
+                &format!("{}.jpg", list_filename),
+                list_filename,
+            ])
+            .assert()
+            .success();
+
+        // An EDL names the files of its events, and uses the ranges of them their source timecodes
+        // select: half a second of the first file, and 0.2 seconds of the second
+        let edl_file = tmp_dir.child("list.edl");
+        let edl_vtt_file = tmp_dir.child("list.vtt");
+        let black_filename = fixture(
+            "black.mkv",
+            "videotestsrc num-buffers=20 pattern=black ! videoconvert ! vp8enc ! \
+             matroskamux ! filesink location={}",
+        );
+        edl_file
+            .write_str(&format!(
+                "TITLE: List\nFCM: NON-DROP FRAME\n\n\
+                 001  AX       V     C        00:00:00:00 00:00:00:15 00:00:00:00 00:00:00:15\n\
+                 * FROM CLIP NAME: {}\n\n\
+                 002  AX       V     C        00:00:00:05 00:00:00:11 00:00:00:15 00:00:00:21\n\
+                 * FROM CLIP NAME: {}\n",
+                filename, black_filename
+            ))
+            .expect("Could not write EDL");
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for concatenate, part 4")
+            .args(&[
+                "-w",
+                "160",
+                "--thumbnails",
+                edl_vtt_file
+                    .path()
+                    .to_str()
+                    .expect("Could not convert EDL VTT filename to str"),
+                edl_file
+                    .path()
+                    .to_str()
+                    .expect("Could not convert EDL filename to str"),
+            ])
+            .assert()
+            .success();
+        edl_vtt_file.assert(
+            predicate::str::contains(" --> 00:00.")
+                .and(predicate::str::contains(" --> 00:01.").not())
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn image_sequence() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for sequence");
+        let frames_dir = tmp_dir.child("frames");
+        frames_dir
+            .create_dir_all()
+            .expect("Could not create directory for frames");
+        let frames_dirname = frames_dir
+            .path()
+            .to_str()
+            .expect("Could not convert frames directory name to str");
+        let vtt_file = tmp_dir.child("test.vtt");
+        let vtt_filename = vtt_file
+            .path()
+            .to_str()
+            .expect("Could not convert VTT filename to str");
+
+        create_file(&format!(
+            "videotestsrc num-buffers=10 ! pngenc ! multifilesink location={}/frame_%04d.png",
+            frames_dirname
+        ));
+
+        fail_with_file("--framerate 0");
+        fail_with_file("--framerate 25/0");
+        // Videos have their own framerate
+        fail_with_file("--framerate 25");
+
+        // Ten frames at 10 FPS are one second long
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for sequence")
+            .args(&[
+                "-w",
+                "160",
+                "--framerate",
+                "10",
+                "--thumbnails",
+                vtt_filename,
+                frames_dirname,
+            ])
+            .assert()
+            .success();
+        vtt_file.assert(
+            predicate::str::contains(" --> 00:00.9")
+                .from_utf8()
+                .from_file_path(),
+        );
+
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for sequence, part 2")
+            .args(&[&format!("{}/frame_*.png", frames_dirname)])
+            .assert()
+            .success();
+        tmp_dir
+            .child("frames.timeline.jpg")
+            .assert(predicate::path::is_file());
+
+        // Files which aren't images can't be decoded as a sequence
+        tmp_dir
+            .child("notes")
+            .create_dir_all()
+            .expect("Could not create directory for notes");
+        for i in 1..3 {
+            tmp_dir
+                .child(format!("notes/note_{}.txt", i))
+                .write_str("Not an image")
+                .expect("Could not write note");
+        }
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for sequence, part 3")
+            .args(&[&format!("{}/notes/note_*.txt", tmp_dir.path().display())])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("not in a supported format"));
+    }
+
+    #[test]
+    fn stdin() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for stdin");
+        let timeline_file = tmp_dir.child("test.jpg");
+        let timeline_filename = timeline_file
+            .path()
+            .to_str()
+            .expect("Could not convert timeline filename to str");
+        let html_file = tmp_dir.child("test.html");
+        let html_filename = html_file
+            .path()
+            .to_str()
+            .expect("Could not convert HTML filename to str");
+
+        fail_with_stdin(&format!("--timeline {} --stream 0", timeline_filename));
+        fail_with_stdin(&format!(
+            "--timeline {} --html {}",
+            timeline_filename, html_filename
+        ));
+
+        ok_with_stdin(&format!("--timeline {}", timeline_filename));
+        timeline_file.assert(predicate::path::is_file());
+
+        ok_with_stdin(&format!(
+            "--start 0.2 --crop 0,0,0,0 --timeline {}",
+            timeline_filename
+        ));
+        // Detecting borders needs to seek
+        fail_with_stdin(&format!("--crop auto --timeline {}", timeline_filename));
+    }
+
+    #[test]
+    fn deinterlace() {
+        fail_with_file("--deinterlace");
+        fail_with_file("--deinterlace foo");
+        fail_with_file("--deinterlace-method");
+        fail_with_file("--deinterlace-method foo");
+
+        ok_with_file("--deinterlace auto");
+        ok_with_file("--deinterlace on");
+        ok_with_file("--deinterlace off");
+        ok_with_file("--deinterlace on --deinterlace-method linear");
+        fail_with_file("--deinterlace off --deinterlace-method linear");
+
+        // Render the thumbnails of `filename` with the deinterlacing `mode`
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for deinterlace");
+        let thumbnails = |name: &str, filename: &str, mode: &str| {
+            let vtt_file = tmp_dir.child(format!("{}-{}.vtt", name, mode));
+            Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .expect("Could not set up binary for deinterlace")
+                .args(&[
+                    "--thumbnails",
+                    vtt_file
+                        .path()
+                        .to_str()
+                        .expect("Could not convert VTT filename to str"),
+                    "--deinterlace",
+                    mode,
+                    filename,
+                ])
+                .assert()
+                .success();
+            fs::read(tmp_dir.child(format!("{}-{}-00.jpg", name, mode)).path())
+                .expect("Could not read thumbnails")
+        };
+
+        // By default, progressive frames are left alone
+        assert_test_file_exists();
+        let filename = test_file_name();
+        assert_eq!(
+            thumbnails("progressive", &filename, "auto"),
+            thumbnails("progressive", &filename, "off")
+        );
+
+        // But the fields of the moving ball in interlaced frames are combined
+        let interlaced_filename = fixture(
+            "interlaced.mkv",
+            "videotestsrc num-buffers=20 pattern=ball ! \
+             video/x-raw,format=I420,width=320,height=240 ! interlace ! matroskamux ! \
+             filesink location={}",
+        );
+        assert_ne!(
+            thumbnails("interlaced", &interlaced_filename, "auto"),
+            thumbnails("interlaced", &interlaced_filename, "off")
+        );
+    }
+
+    #[test]
+    fn audio() {
+        fail_with_file("--audio spectrum");
+        fail_with_file("--audio-height 20");
+        fail_with_file("--thumbnails test.vtt --audio waveform");
+
+        // Without an audio track, the strip stays empty
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for audio");
+        let timeline_file = tmp_dir.child("test.jpg");
+        let timeline_filename = timeline_file
+            .path()
+            .to_str()
+            .expect("Could not convert timeline filename to str");
+        ok_with_file(&format!(
+            "--audio waveform --timeline {}",
+            timeline_filename
+        ));
+        timeline_file.assert(predicate::path::exists());
+
+        let filename = fixture(
+            "audio.mkv",
+            "videotestsrc num-buffers=20 ! videoconvert ! vp8enc ! matroskamux name=mux ! \
+             filesink location={} audiotestsrc num-buffers=29 ! audioconvert ! vorbisenc ! \
+             mux.",
+        );
+
+        let strip_file = tmp_dir.child("audio.jpg");
+        let strip_filename = strip_file
+            .path()
+            .to_str()
+            .expect("Could not convert audio strip filename to str");
+        let manifest_file = tmp_dir.child("test.json");
+        let manifest_filename = manifest_file
+            .path()
+            .to_str()
+            .expect("Could not convert manifest filename to str");
+
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for audio")
+            .args(&[
+                "--audio",
+                "loudness",
+                "--audio-strip",
+                strip_filename,
+                "--manifest",
+                manifest_filename,
+                &filename,
+            ])
+            .assert()
+            .success();
+        strip_file.assert(predicate::path::exists());
+        manifest_file.assert(
+            predicate::str::contains("\"style\": \"loudness\"")
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn audio_only() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for audio only");
+        let timeline_file = tmp_dir.child("test.jpg");
+        let timeline_filename = timeline_file
+            .path()
+            .to_str()
+            .expect("Could not convert timeline filename to str");
+        let manifest_file = tmp_dir.child("test.json");
+        let manifest_filename = manifest_file
+            .path()
+            .to_str()
+            .expect("Could not convert manifest filename to str");
+
+        let filename = fixture(
+            "audio_only.ogg",
+            "audiotestsrc num-buffers=100 wave=pink-noise ! audioconvert ! vorbisenc ! \
+             oggmux ! filesink location={}",
+        );
+
+        // Audio files are shown as a spectrogram
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for audio only")
+            .args(&[
+                "--timeline",
+                timeline_filename,
+                "--manifest",
+                manifest_filename,
+                &filename,
+            ])
+            .assert()
+            .success();
+        timeline_file.assert(predicate::path::exists());
+        manifest_file.assert(
+            predicate::str::contains("\"audio_only\": true")
+                .from_utf8()
+                .from_file_path(),
+        );
+
+        // But they don't have thumbnails
+        let vtt_file = tmp_dir.child("test.vtt");
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for audio only, part 2")
+            .args(&[
+                "--thumbnails",
+                vtt_file
+                    .path()
+                    .to_str()
+                    .expect("Could not convert VTT filename to str"),
+                &filename,
+            ])
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn cuts() {
+        fail_with_file("--cuts nope.txt");
+        fail_with_file("--cut-threshold 0.5");
+        fail_with_file("--cuts test.csv --cut-threshold 0");
+        fail_with_file("--cuts test.csv --cut-threshold 1.5");
+        fail_with_file("--thumbnails test.vtt --cut-markers");
+
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for cuts");
+        let timeline_file = tmp_dir.child("test.jpg");
+        let timeline_filename = timeline_file
+            .path()
+            .to_str()
+            .expect("Could not convert timeline filename to str");
+
+        assert_test_file_exists();
+        let filename = test_file_name();
+        let black_filename = fixture(
+            "black.mkv",
+            "videotestsrc num-buffers=20 pattern=black ! videoconvert ! vp8enc ! \
+             matroskamux ! filesink location={}",
+        );
+
+        // The color bars are followed by a black shot, so there is exactly one cut
+        for (extension, expected) in &[
+            ("csv", "\n1,0."),
+            ("edl", "002  AX       V     C        00:00:00:"),
+            ("vtt", "\nShot 2\n"),
+        ] {
+            let cuts_file = tmp_dir.child(format!("cuts.{}", extension));
+            Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .expect("Could not set up binary for cuts")
+                .args(&[
+                    "--timeline",
+                    timeline_filename,
+                    "--cut-markers",
+                    "--cuts",
+                    cuts_file
+                        .path()
+                        .to_str()
+                        .expect("Could not convert cuts filename to str"),
+                    &filename,
+                    &black_filename,
+                ])
+                .assert()
+                .success();
+            cuts_file.assert(
+                predicate::str::contains(*expected)
+                    .from_utf8()
+                    .from_file_path(),
+            );
+            cuts_file.assert(
+                predicate::str::contains("Shot 3")
+                    .not()
+                    .from_utf8()
+                    .from_file_path(),
+            );
+        }
+        timeline_file.assert(predicate::path::exists());
+    }
+
+    #[test]
+    fn qc_report() {
+        fail_with_file("--qc-report nope.txt");
+        fail_with_file("--qc-overlay");
+        fail_with_file("--thumbnails test.vtt --qc-report test.json --qc-overlay");
+
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for QC report");
+        let timeline_file = tmp_dir.child("test.jpg");
+        let report_file = tmp_dir.child("qc.json");
+
+        assert_test_file_exists();
+        let filename = test_file_name();
+        let black_filename = fixture(
+            "black.mkv",
+            "videotestsrc num-buffers=20 pattern=black ! videoconvert ! vp8enc ! \
+             matroskamux ! filesink location={}",
+        );
+
+        // The black part starts after the color bars, which last about 0.67 seconds
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for QC report")
+            .args(&[
+                "--timeline",
+                timeline_file
+                    .path()
+                    .to_str()
+                    .expect("Could not convert timeline filename to str"),
+                "--qc-report",
+                report_file
+                    .path()
+                    .to_str()
+                    .expect("Could not convert QC report filename to str"),
+                "--qc-overlay",
+                &filename,
+                &black_filename,
+            ])
+            .assert()
+            .success();
+        timeline_file.assert(predicate::path::exists());
+        report_file.assert(
+            predicate::str::contains("\"black\": [\n    {\n      \"start\": 0.")
+                .from_utf8()
+                .from_file_path(),
+        );
+        report_file.assert(
+            predicate::str::contains("\"frozen\": []")
+                .from_utf8()
+                .from_file_path(),
+        );
+        report_file.assert(
+            predicate::str::contains("\"silence\": null")
+                .from_utf8()
+                .from_file_path(),
+        );
+
+        // The test file has no audio track, so nothing is known about silence
+        ok_with_file(&format!(
+            "--qc-report {} --audio waveform",
+            report_file
+                .path()
+                .to_str()
+                .expect("Could not convert QC report filename to str")
+        ));
+        report_file.assert(
+            predicate::str::contains("\"silence\": null")
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn flash_report() {
+        fail_with_file("--flash-report nope.txt");
+        fail_with_file("--flash-markers");
+
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for flash report");
+        let timeline_file = tmp_dir.child("test.jpg");
+        let report_file = tmp_dir.child("flash.json");
+        let report_filename = report_file
+            .path()
+            .to_str()
+            .expect("Could not convert flash report filename to str");
+
+        // The test file doesn't flash
+        ok_with_file(&format!("--flash-report {}", report_filename));
+        report_file.assert(
+            predicate::str::contains("\"luminance\": []")
+                .from_utf8()
+                .from_file_path(),
+        );
+
+        // But switching between black and white ten times per second does
+        let filename = fixture(
+            "flash.mkv",
+            "videotestsrc num-buffers=40 pattern=blink ! video/x-raw,framerate=10/1 ! \
+             videoconvert ! vp8enc ! matroskamux ! filesink location={}",
+        );
+
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for flash report")
+            .args(&[
+                "--timeline",
+                timeline_file
+                    .path()
+                    .to_str()
+                    .expect("Could not convert timeline filename to str"),
+                "--flash-report",
+                report_filename,
+                "--flash-markers",
+                &filename,
+            ])
+            .assert()
+            .success()
+            .stdout(
+                predicate::str::contains("warning")
+                    .and(predicate::str::contains(": harmful flashing found"))
+                    .and(predicate::str::contains("no harmful flashing").not()),
+            );
+        timeline_file.assert(predicate::path::exists());
+        report_file.assert(
+            predicate::str::contains("\"luminance\": [\n    {")
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn palette() {
+        fail_with_file("--palette nope.txt");
+        fail_with_file("--palette-swatches nope.png");
+        fail_with_file("--palette-colors 3");
+        fail_with_file("--palette test.json --palette-colors 0");
+        fail_with_file("--palette test.json --palette-interval 0");
+        fail_with_file("--palette test.json --palette-interval foo");
+
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for palette");
+        let palette_file = tmp_dir.child("palette.json");
+        let palette_filename = palette_file
+            .path()
+            .to_str()
+            .expect("Could not convert palette filename to str");
+        let swatches_file = tmp_dir.child("swatches.jpg");
+        let swatches_filename = swatches_file
+            .path()
+            .to_str()
+            .expect("Could not convert swatches filename to str");
+
+        // The test file shows the same color bars all the time, so it is a single scene. Scenes
+        // are found by cut detection, which can be tuned.
+        ok_with_file(&format!(
+            "--palette {} --palette-swatches {} --cut-threshold 0.5",
+            palette_filename, swatches_filename
+        ));
+        swatches_file.assert(predicate::path::is_file());
+        palette_file.assert(
+            predicate::str::contains("\"palette\": [\n    {\n      \"color\": \"#")
+                .and(predicate::str::contains("\"segments\": [\n    {"))
+                .and(predicate::str::contains("\"interval\": null"))
+                .from_utf8()
+                .from_file_path(),
+        );
+
+        ok_with_file(&format!(
+            "--palette {} --palette-colors 3 --palette-interval 0.2",
+            palette_filename
+        ));
+        palette_file.assert(
+            predicate::str::contains("\"interval\": 0.2")
+                .and(predicate::str::contains("\"start\": 0.2,"))
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn fingerprint() {
+        fail_with_file("--fingerprint nope.txt");
+        fail_with_file("--compare");
+        fail_with_file("--compare does_not_exist.123");
+
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for fingerprint");
+        let fingerprint_file = tmp_dir.child("fingerprint.csv");
+        let fingerprint_filename = fingerprint_file
+            .path()
+            .to_str()
+            .expect("Could not convert fingerprint filename to str");
+
+        ok_with_file(&format!("--fingerprint {}", fingerprint_filename));
+        fingerprint_file.assert(
+            predicate::str::starts_with("time,red,green,blue\n")
+                .from_utf8()
+                .from_file_path(),
+        );
+
+        // Nothing else can be generated while comparing
+        fail_with_file(&format!(
+            "--compare {} --timeline test.jpg",
+            fingerprint_filename
+        ));
+
+        assert_test_file_exists();
+        let filename = test_file_name();
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for comparison")
+            .args(&["--compare", fingerprint_filename, &filename])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("fingerprinting"));
+
+        // The colors of this file keep changing, so it matches its own fingerprint exactly
+        let ball_filename = fixture(
+            "ball.mkv",
+            "videotestsrc num-buffers=150 pattern=ball flip=true ! \
+             video/x-raw,width=320,height=240 ! videoconvert ! vp8enc ! matroskamux ! \
+             filesink location={}",
+        );
+        let ball_fingerprint_file = tmp_dir.child("ball.csv");
+        let ball_fingerprint_filename = ball_fingerprint_file
+            .path()
+            .to_str()
+            .expect("Could not convert ball fingerprint filename to str");
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for comparison, part 2")
+            .args(&["--fingerprint", ball_fingerprint_filename, &ball_filename])
+            .assert()
+            .success();
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for comparison, part 3")
+            .args(&["--compare", ball_fingerprint_filename, &ball_filename])
+            .assert()
+            .success()
+            .stdout(
+                predicate::str::contains("-> similarity: 1.000")
+                    .and(predicate::str::contains("corresponds to 1.0000 * t")),
+            );
+    }
+
+    #[test]
+    fn crop() {
+        fail_with_file("--crop");
+        fail_with_file("--crop foo");
+        fail_with_file("--crop 1,2,3");
+        fail_with_file("--crop 1,2,3,-4");
+        fail_with_file("--crop 1,2,3,4,5");
+        fail_with_file("--crop 120,0,120,0");
+        fail_with_file("--crop 0,160,0,160");
+
+        ok_with_file("--crop auto");
+        ok_with_file("--crop auto --start 0.2 --end 0.5");
+        ok_with_file("--crop none");
+        ok_with_file("--crop 0,0,0,0");
+        ok_with_file("--crop 30,0,30,0");
+    }
+
     #[test]
     fn timeline_file() {
         let filename = test_file_name();
@@ -193,6 +1071,216 @@ mod integration {
                 .from_utf8()
                 .from_file_path(),
         );
+
+        fail_with_file("--pick-thumbnails");
+
+        // Thumbnails can only be picked if it is known which column a frame belongs to
+        let vtt_filename = vtt_file
+            .path()
+            .to_str()
+            .expect("Could not convert VTT filename to str");
+        fail_with_file(&format!(
+            "--thumbnails {} --pick-thumbnails --ignore-duration",
+            vtt_filename
+        ));
+        fail_with_stdin(&format!("--thumbnails {} --pick-thumbnails", vtt_filename));
+
+        // Only the first frame is black. At 10 FPS, the first of the 16 columns has two more
+        // frames, so a better thumbnail than the first frame can be picked for it.
+        let frames_dir = tmp_dir.child("frames");
+        frames_dir
+            .create_dir_all()
+            .expect("Could not create directory for frames");
+        let frames_dirname = frames_dir
+            .path()
+            .to_str()
+            .expect("Could not convert frames directory name to str");
+        create_file(&format!(
+            "videotestsrc num-buffers=1 pattern=black ! video/x-raw,width=320,height=240 ! \
+             pngenc ! multifilesink location={}/frame_%04d.png",
+            frames_dirname
+        ));
+        create_file(&format!(
+            "videotestsrc num-buffers=39 ! video/x-raw,width=320,height=240 ! pngenc ! \
+             multifilesink index=1 location={}/frame_%04d.png",
+            frames_dirname
+        ));
+
+        // Returns the first grid of the thumbnails, which are named after `name`
+        let first_grid = |name: &str, args: &[&str]| {
+            let vtt_file = tmp_dir.child(format!("{}.vtt", name));
+            Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .expect("Could not set up binary for picking thumbnails")
+                .args(&[
+                    "-w",
+                    "16",
+                    "--framerate",
+                    "10",
+                    "--thumbnails",
+                    vtt_file
+                        .path()
+                        .to_str()
+                        .expect("Could not convert VTT filename to str"),
+                ])
+                .args(args)
+                .arg(frames_dirname)
+                .assert()
+                .success();
+            fs::read(tmp_dir.child(format!("{}-00.jpg", name)).path())
+                .expect("Could not read first grid")
+        };
+        assert_ne!(
+            first_grid("first", &[]),
+            first_grid("picked", &["--pick-thumbnails", "--cut-threshold", "0.5"])
+        );
+    }
+
+    #[test]
+    fn dedup_thumbnails() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for dedup");
+        let vtt_file = tmp_dir.child("test.vtt");
+        let first_grid_file = tmp_dir.child("test-00.jpg");
+        let second_grid_file = tmp_dir.child("test-01.jpg");
+
+        fail_with_file("--dedup-thumbnails");
+
+        // The test file shows the same color bars all the time, so a single tile is enough
+        assert_test_file_exists();
+        let filename = test_file_name();
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary for dedup")
+            .args(&[
+                "--thumbnails",
+                vtt_file
+                    .path()
+                    .to_str()
+                    .expect("Could not convert VTT filename to str"),
+                "--dedup-thumbnails",
+                &filename,
+            ])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("thumbnails are duplicates"));
+        first_grid_file.assert(predicate::path::is_file());
+        second_grid_file.assert(predicate::path::missing());
+        vtt_file.assert(
+            predicate::str::contains("test-00.jpg?xywh=0,0,")
+                .from_utf8()
+                .from_file_path(),
+        );
+        vtt_file.assert(
+            predicate::str::is_match("xywh=(\\d+,)?[1-9]")
+                .expect("Could not compile tile pattern")
+                .not()
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn bif() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for BIF");
+        let bif_file = tmp_dir.child("test.bif");
+        let bif_filename = bif_file
+            .path()
+            .to_str()
+            .expect("Could not convert BIF filename to str");
+
+        fail_with_file("--bif nope.jpg");
+        fail_with_file("--bif-interval 5");
+        fail_with_file(&format!("--bif {} --bif-interval 0", bif_filename));
+        fail_with_file(&format!("--bif {} --bif-interval 1.5", bif_filename));
+        fail_with_file(&format!("--bif {} --bif-interval nope", bif_filename));
+
+        ok_with_file(&format!("--bif {} -H 16", bif_filename));
+        ok_with_file(&format!("--bif {} --bif-interval 1", bif_filename));
+
+        bif_file.assert(predicate::path::is_file());
+    }
+
+    #[test]
+    fn manifest() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for manifest");
+        let vtt_file = tmp_dir.child("test.vtt");
+        let manifest_file = tmp_dir.child("test.json");
+        let manifest_filename = manifest_file
+            .path()
+            .to_str()
+            .expect("Could not convert manifest filename to str");
+
+        fail_with_file("--manifest nope.txt");
+
+        ok_with_file(&format!(
+            "--manifest {} --thumbnails {}",
+            manifest_filename,
+            &vtt_file
+                .path()
+                .to_str()
+                .expect("Could not convert VTT filename to str"),
+        ));
+
+        manifest_file.assert(predicate::path::is_file());
+        manifest_file.assert(
+            predicate::str::contains("\"xywh\": [0, 0, ")
+                .from_utf8()
+                .from_file_path(),
+        );
+        manifest_file.assert(
+            predicate::str::contains("test-00.jpg")
+                .from_utf8()
+                .from_file_path(),
+        );
+        manifest_file.assert(
+            predicate::str::is_match("\"hash\": \"[0-9a-f]{16}\"")
+                .expect("Could not compile hash pattern")
+                .from_utf8()
+                .from_file_path(),
+        );
+    }
+
+    #[test]
+    fn html() {
+        let tmp_dir = assert_fs::TempDir::new().expect("Could not make tempdir for HTML");
+        let vtt_file = tmp_dir.child("test.vtt");
+        let html_file = tmp_dir.child("preview.html");
+        let html_filename = html_file
+            .path()
+            .to_str()
+            .expect("Could not convert HTML filename to str");
+
+        let timeline_file = tmp_dir.child("timeline.jpg");
+        let timeline_filename = timeline_file
+            .path()
+            .to_str()
+            .expect("Could not convert timeline filename to str");
+        let vtt_filename = vtt_file
+            .path()
+            .to_str()
+            .expect("Could not convert VTT filename to str");
+
+        fail_with_file(&format!("--html nope.htm --timeline {}", timeline_filename));
+        // The page needs a timeline, which isn't written without being asked for
+        fail_with_file(&format!(
+            "--html {} --thumbnails {}",
+            html_filename, vtt_filename
+        ));
+
+        ok_with_file(&format!(
+            "--html {} --timeline {} --thumbnails {}",
+            html_filename, timeline_filename, vtt_filename
+        ));
+
+        html_file.assert(predicate::path::is_file());
+        html_file.assert(
+            predicate::str::contains("\"timeline.jpg\"")
+                .from_utf8()
+                .from_file_path(),
+        );
+        html_file.assert(
+            predicate::str::contains("\"test-00.jpg\"")
+                .from_utf8()
+                .from_file_path(),
+        );
     }
 
     fn test_file_name() -> String {
@@ -206,11 +1294,62 @@ mod integration {
         )
     }
 
+    // Returns the path of a test file called `name`, which the GStreamer `pipeline` writes to
+    // the location `{}`. It is only created if it doesn't exist yet, and its name contains a hash
+    // of the pipeline, so that changing the pipeline creates a new file.
+    fn fixture(name: &str, pipeline: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        pipeline.hash(&mut hasher);
+        let name = Path::new(name);
+        let mut filename = env::temp_dir();
+        filename.push(format!(
+            "timelens_test_{}_{:016x}.{}",
+            name.file_stem()
+                .and_then(|stem| stem.to_str())
+                .expect("Could not get stem of fixture name"),
+            hasher.finish(),
+            name.extension()
+                .and_then(|extension| extension.to_str())
+                .expect("Could not get extension of fixture name"),
+        ));
+        let filename = String::from(
+            filename
+                .as_path()
+                .to_str()
+                .expect("Could not convert fixture file name to str"),
+        );
+
+        // Tests run in parallel and may need the same fixture, so each of them writes to its own
+        // partial file, which only replaces the fixture once it is complete
+        if !Path::new(&filename).exists() {
+            static PARTIAL_FILES: AtomicUsize = AtomicUsize::new(0);
+            let partial_filename = format!(
+                "{}.{}-{}.part",
+                filename,
+                process::id(),
+                PARTIAL_FILES.fetch_add(1, Ordering::SeqCst)
+            );
+            create_file(&pipeline.replace("{}", &partial_filename));
+            fs::rename(&partial_filename, &filename).expect("Could not rename fixture");
+        }
+        filename
+    }
+
     fn create_test_file() {
         let filename = test_file_name();
 
+        create_file(&format!(
+            "videotestsrc num-buffers=20 ! videoconvert ! vp8enc ! matroskamux ! filesink \
+             location={}",
+            &filename
+        ));
+    }
+
+    // Run the GStreamer pipeline `description` until it's done
+    fn create_file(description: &str) {
         gst::init().expect("Could not initialize GStreamer");
-        let pipeline = gst::parse_launch(&format!("videotestsrc num-buffers=20 ! videoconvert ! vp8enc ! matroskamux ! filesink location={}", &filename)).expect("Could not parse-launch pipeline for creating test file");
+        let pipeline = gst::parse_launch(description)
+            .expect("Could not parse-launch pipeline for creating test file");
 
         pipeline
             .set_state(gst::State::Playing)
@@ -239,6 +1378,58 @@ mod integration {
             .expect("Could not stop creating pipeline");
     }
 
+    // Serve the test file via HTTP on a random local port, and return the server's URL. The server
+    // ignores range requests, and answers every path except `/timelens_test.mkv` with a 404.
+    fn serve_test_file() -> String {
+        assert_test_file_exists();
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind test server");
+        let address = listener
+            .local_addr()
+            .expect("Could not get address of test server");
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+
+                thread::spawn(move || {
+                    // Read the request header
+                    let mut request = vec![];
+                    let mut buffer = [0; 1024];
+                    while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                        match stream.read(&mut buffer) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buffer[..n]),
+                        }
+                    }
+
+                    let request = String::from_utf8_lossy(&request);
+                    let response = if request.starts_with("GET /timelens_test.mkv ") {
+                        let content = fs::read(test_file_name()).expect("Could not read test file");
+                        let mut response = format!(
+                            "HTTP/1.0 200 OK\r\nContent-Type: video/x-matroska\r\n\
+                             Content-Length: {}\r\n\r\n",
+                            content.len()
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(&content);
+                        response
+                    } else {
+                        b"HTTP/1.0 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec()
+                    };
+
+                    // The client might hang up early, which is fine
+                    let _ = stream.write_all(&response);
+                });
+            }
+        });
+
+        format!("http://{}", address)
+    }
+
     fn assert_test_file_exists() {
         let filename = test_file_name();
 
@@ -280,6 +1471,41 @@ mod integration {
             .success();
     }
 
+    fn ok_with_stdin(args_string: &str) {
+        run_with_stdin(args_string).assert().success();
+    }
+
+    fn fail_with_stdin(args_string: &str) {
+        run_with_stdin(args_string).assert().failure();
+    }
+
+    // Run timelens on the test file, which is written to its standard input through a pipe, so
+    // that it can't be seeked like a redirected file
+    fn run_with_stdin(args_string: &str) -> std::process::Output {
+        assert_test_file_exists();
+
+        let data = fs::read(test_file_name()).expect("Could not read test file");
+        let mut args: Vec<&str> = args_string.split(' ').collect();
+        args.push("-");
+        let mut child = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("Could not set up binary, part 5")
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Could not start binary");
+
+        // Timelens may exit before it has read everything, so write errors are ignored
+        let mut stdin = child.stdin.take().expect("Could not open stdin");
+        let writer = thread::spawn(move || {
+            let _ = stdin.write_all(&data);
+        });
+        let output = child.wait_with_output().expect("Could not wait for binary");
+        writer.join().expect("Could not join writer thread");
+        output
+    }
+
     fn fail_with_file(args_string: &str) {
         assert_test_file_exists();
 